@@ -0,0 +1,22 @@
+//! Compares finding what the ball touches by testing every brick against going through
+//! the grid of the broadphase, for more and more bricks:
+//! `cargo bench --bench broadphase --features bench`.
+//! Testing every brick gets slower with every brick added, while the grid stays about as fast.
+
+use breakout_bevy::bench::time_lookups;
+
+const BRICK_COUNTS: [usize; 5] = [20, 100, 1_000, 10_000, 50_000];
+const LOOKUPS: usize = 10_000;
+
+fn main() {
+    println!("bricks,every_brick_ns,grid_ns,speedup");
+    for bricks in BRICK_COUNTS {
+        let (every_brick, grid) = time_lookups(bricks, LOOKUPS);
+        println!(
+            "{bricks},{},{},{:.1}",
+            every_brick.as_nanos(),
+            grid.as_nanos(),
+            every_brick.as_secs_f64() / grid.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! The size of the arena, which a level may change from the usual one for narrow or wide arenas.
+//!
+//! The paddles, the grid of bricks and the camera all follow the `Arena` resource, and the walls
+//! the `WallOutline` one, so changing them between the levels of a pack rebuilds the arena
+//! around the new level.
+
+use bevy::{prelude::*, window::WindowResized};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    paddle_spots,
+    tunables::Tunables,
+    wall::{spawn_walls, LineMaterial, WallOutline},
+    Ball, GameMode, PaddleFilter, PaddleLane, Player, Wall, CAMERA_POSITION,
+    GAP_BETWEEN_PADDLE_AND_FLOOR, WALL_THICKNESS,
+};
+
+/// Anything smaller leaves no room between the paddle and the bricks
+pub(crate) const MIN_ARENA_SIZE: Vec2 = Vec2::new(300.0, 400.0);
+
+// How much room to leave around the arena when fitting the camera to the window
+const CAMERA_MARGIN: f32 = 1.1;
+
+/// The inside of the walls, centered on the origin, in `Transform` units
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct Arena {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Arena {
+            width: 900.0,
+            height: 600.0,
+        }
+    }
+}
+
+impl Arena {
+    pub(crate) fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+
+    // x coordinates
+    pub(crate) fn left(&self) -> f32 {
+        -self.width / 2.0
+    }
+
+    pub(crate) fn right(&self) -> f32 {
+        self.width / 2.0
+    }
+
+    // y coordinates
+    pub(crate) fn bottom(&self) -> f32 {
+        -self.height / 2.0
+    }
+
+    pub(crate) fn top(&self) -> f32 {
+        self.height / 2.0
+    }
+
+    /// Height of the paddle defending the bottom wall
+    pub(crate) fn paddle_y(&self) -> f32 {
+        self.bottom() + GAP_BETWEEN_PADDLE_AND_FLOOR
+    }
+
+    /// Height of the paddle defending the top wall, in modes that have one
+    pub(crate) fn top_paddle_y(&self) -> f32 {
+        self.top() - GAP_BETWEEN_PADDLE_AND_FLOOR
+    }
+
+    pub(crate) fn contains(&self, position: Vec2) -> bool {
+        position.x >= self.left()
+            && position.x <= self.right()
+            && position.y >= self.bottom()
+            && position.y <= self.top()
+    }
+
+    /// The same arena, grown to the smallest size that can be played in
+    pub(crate) fn at_least_min_size(self) -> Self {
+        let size = self.size().max(MIN_ARENA_SIZE);
+        Arena {
+            width: size.x,
+            height: size.y,
+        }
+    }
+}
+
+// Rebuilds the walls when they change, and moves the paddles and ball over when the arena does,
+// e.g. for the next level of a pack. The bricks come with the level.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fit_arena(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    walls: Res<WallOutline>,
+    mode: Res<GameMode>,
+    tunables: Res<Tunables>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    wall_query: Query<Entity, With<Wall>>,
+    mut paddle_query: Query<(&Player, &mut Transform, &mut PaddleLane), PaddleFilter>,
+    mut ball_query: Query<&mut Transform, With<Ball>>,
+) {
+    // The walls and paddles are spawned to fit in the first place
+    if walls.is_changed() && !walls.is_added() {
+        for wall in &wall_query {
+            commands.entity(wall).despawn();
+        }
+        spawn_walls(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &tunables,
+            *mode,
+            &walls,
+        );
+    }
+    if !arena.is_changed() || arena.is_added() {
+        return;
+    }
+
+    let spots = paddle_spots(*mode, &arena);
+    for (player, mut transform, mut lane) in &mut paddle_query {
+        if let Some(&(_, spot_lane, y)) = spots.iter().find(|(spot, ..)| spot == player) {
+            *lane = spot_lane;
+            transform.translation.x = lane.center();
+            transform.translation.y = y;
+        }
+    }
+
+    for mut transform in &mut ball_query {
+        transform.translation = mode.ball_starting_position(&tunables, &arena);
+    }
+}
+
+// Backs the camera up or brings it closer, so the whole arena fits in the window
+// whatever its size and shape. The camera keeps looking at the arena from the same angle.
+pub(crate) fn fit_camera(
+    arena: Res<Arena>,
+    windows: Res<Windows>,
+    mut resized_events: EventReader<WindowResized>,
+    mut camera_query: Query<(&mut Transform, &mut Projection), With<Camera3d>>,
+) {
+    let resized = resized_events.iter().count() > 0;
+    if !resized && !arena.is_changed() {
+        return;
+    }
+    let Some(window) = windows.get_primary() else {
+        return;
+    };
+    // Minimized
+    if window.width() <= 0.0 || window.height() <= 0.0 {
+        return;
+    }
+    let aspect_ratio = window.width() / window.height();
+
+    let half_size = (arena.size() + WALL_THICKNESS) / 2.0 * CAMERA_MARGIN;
+    for (mut transform, mut projection) in &mut camera_query {
+        let Projection::Perspective(perspective) = &mut *projection else {
+            continue;
+        };
+        // The field of view is vertical, and widens with the window
+        let tan_half_fov = (perspective.fov / 2.0).tan();
+        let distance =
+            (half_size.y / tan_half_fov).max(half_size.x / (tan_half_fov * aspect_ratio));
+
+        *transform = Transform::from_translation(CAMERA_POSITION.normalize() * distance)
+            .looking_at(Vec3::ZERO, Vec3::Y);
+        // Big arenas would otherwise end up beyond the far plane
+        perspective.far = perspective.far.max(distance + half_size.length() * 2.0);
+    }
+}
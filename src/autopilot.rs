@@ -0,0 +1,161 @@
+//! A computer controller for the paddle.
+//! It predicts where the ball will come down by following its velocity
+//! through the bounces off the arena walls, and moves the paddle there.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena,
+    collision::{Collider, Segment},
+    rng::SimRng,
+    tunables::Tunables,
+    Ball, Paddle, PaddleInput, PlayerControlled, Velocity, Wall, BALL_RADIUS, TIME_STEP,
+};
+
+// How many wall bounces to follow before giving up on a prediction
+const MAX_PREDICTED_BOUNCES: usize = 8;
+// Looking at the ball more often than it moves wouldn't help
+const MIN_REACTION_TIME: f32 = TIME_STEP;
+
+/// Steers a paddle on its own instead of the keyboard
+#[derive(Component, Clone)]
+pub(crate) struct Autopilot {
+    /// Seconds between two looks at the ball, at least one step.
+    /// In between, the paddle keeps heading for the last prediction.
+    pub(crate) reaction_time: f32,
+    /// How far off the aim can be, in `Transform` units
+    pub(crate) max_error: f32,
+    reaction: Timer,
+    target_x: Option<f32>,
+}
+
+impl Autopilot {
+    pub(crate) fn new(reaction_time: f32, max_error: f32) -> Self {
+        // A repeating timer of no length divides by zero once it is ticked
+        let reaction_time = reaction_time.max(MIN_REACTION_TIME);
+        Autopilot {
+            reaction_time,
+            max_error,
+            reaction: Timer::from_seconds(reaction_time, TimerMode::Repeating),
+            target_x: None,
+        }
+    }
+}
+
+impl Default for Autopilot {
+    fn default() -> Self {
+        Autopilot::new(0.15, 20.0)
+    }
+}
+
+// Hand the player's paddle over to the autopilot
+pub(crate) fn take_over_paddle(
+    mut commands: Commands,
+    query: Query<Entity, (With<Paddle>, With<PlayerControlled>)>,
+) {
+    for paddle in &query {
+        commands
+            .entity(paddle)
+            .remove::<PlayerControlled>()
+            .insert(Autopilot::default());
+    }
+}
+
+pub(crate) fn drive_paddle(
+    mut rng: ResMut<SimRng>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    ball_query: Query<(&Transform, &Velocity), With<Ball>>,
+    wall_query: Query<&Collider, With<Wall>>,
+    mut paddle_query: Query<(&Transform, &mut PaddleInput, &mut Autopilot), With<Paddle>>,
+) {
+    let Ok((ball_transform, ball_velocity)) = ball_query.get_single() else {
+        return;
+    };
+
+    // Walls are spawned where they are, so their segments need no moving
+    let walls: Vec<Segment> = wall_query
+        .iter()
+        .filter_map(|collider| match *collider {
+            Collider::Segment(segment) => Some(segment),
+            _ => None,
+        })
+        .collect();
+
+    for (paddle_transform, mut input, mut autopilot) in &mut paddle_query {
+        let reaction_time = Duration::from_secs_f32(autopilot.reaction_time.max(MIN_REACTION_TIME));
+        if autopilot.reaction.duration() != reaction_time {
+            autopilot.reaction.set_duration(reaction_time);
+        }
+        autopilot.reaction.tick(Duration::from_secs_f32(TIME_STEP));
+
+        if autopilot.target_x.is_none() || autopilot.reaction.just_finished() {
+            // The ball is hit when it touches the side of the paddle facing the arena
+            let paddle_y = paddle_transform.translation.y;
+            let reach = tunables.paddle_size.y / 2.0 + BALL_RADIUS;
+            let contact_y = if paddle_y > (arena.top() + arena.bottom()) / 2.0 {
+                paddle_y - reach
+            } else {
+                paddle_y + reach
+            };
+            let landing_x = predict_crossing(
+                ball_transform.translation.truncate(),
+                ball_velocity.0,
+                contact_y,
+                &walls,
+            )
+            // While the ball is out of reach, shadow it
+            .unwrap_or(ball_transform.translation.x);
+
+            let error = rng.range(-autopilot.max_error, autopilot.max_error);
+            autopilot.target_x = Some(landing_x + error);
+        }
+
+        let Some(target_x) = autopilot.target_x else {
+            continue;
+        };
+
+        // Full speed until we are less than a step away, then slow down to land on the target
+        let max_step = tunables.paddle_speed * TIME_STEP;
+        input.0 = ((target_x - paddle_transform.translation.x) / max_step).clamp(-1.0, 1.0);
+    }
+}
+
+/// Follows a ball from `position` along `velocity`, bouncing off the given walls,
+/// and returns the x coordinate at which it next crosses the line at `target_y`.
+pub(crate) fn predict_crossing(
+    mut position: Vec2,
+    mut velocity: Vec2,
+    target_y: f32,
+    walls: &[Segment],
+) -> Option<f32> {
+    for _ in 0..=MAX_PREDICTED_BOUNCES {
+        let mut time_to_target = (target_y - position.y) / velocity.y;
+        // The ball is heading away from the line, or moving parallel to it
+        if time_to_target.is_nan() || time_to_target < 0.0 {
+            time_to_target = f32::INFINITY;
+        }
+
+        // The ball can't get closer to a wall than its own radius,
+        // so move the walls in by that much and follow the ball's center
+        let first_hit = walls
+            .iter()
+            .filter_map(|wall| wall.cast_ray(position, velocity, BALL_RADIUS))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        match first_hit {
+            Some((time, normal)) if time < time_to_target => {
+                position += velocity * time;
+                velocity -= 2.0 * velocity.dot(normal) * normal;
+            }
+            _ if time_to_target.is_finite() => {
+                return Some(position.x + velocity.x * time_to_target);
+            }
+            _ => return None,
+        }
+    }
+
+    None
+}
@@ -0,0 +1,249 @@
+//! Plays many headless games of a level with the autopilot, for the `balance` binary.
+//! The numbers show whether a level is too hard, too easy or too slow,
+//! and which bricks the ball hardly ever gets to.
+
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use serde::Serialize;
+
+use crate::{
+    headless::Simulation,
+    level::{BrickKind, Level},
+    rng::SimRng,
+    tunables::Tunables,
+    Ball, Brick, BrickHitEvent, GameMode, RoundState, Scoreboard, Velocity, STARTING_LIVES,
+    TIME_STEP,
+};
+
+// Ball speeds are counted in buckets this wide, in `Transform` units per second
+const SPEED_BUCKET: f32 = 10.0;
+
+/// What to simulate
+pub struct BalanceConfig<'a> {
+    pub games: usize,
+    /// The usual grid of bricks if there is none
+    pub level: Option<&'a Path>,
+    /// Seeds the seeds of every game, so a whole batch can be run again
+    pub seed: u64,
+    /// Games still going after this long are given up on
+    pub max_seconds: f32,
+}
+
+#[derive(Serialize)]
+pub struct BalanceReport {
+    summary: Summary,
+    games: Vec<GameStats>,
+    bricks: Vec<BrickStats>,
+    /// How many steps the ball spent at each speed, by the lower end of the bucket
+    ball_speeds: BTreeMap<u32, u64>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    games: usize,
+    clear_rate: f32,
+    // Only counting the games that were cleared
+    mean_seconds_to_clear: Option<f32>,
+    mean_lives_lost: f32,
+    timeouts: usize,
+    min_ball_speed: f32,
+    mean_ball_speed: f32,
+    max_ball_speed: f32,
+}
+
+#[derive(Serialize)]
+struct GameStats {
+    seed: u64,
+    cleared: bool,
+    timed_out: bool,
+    seconds: f32,
+    lives_lost: usize,
+    score: usize,
+}
+
+#[derive(Serialize)]
+struct BrickStats {
+    x: f32,
+    y: f32,
+    kind: BrickKind,
+    hits: u64,
+}
+
+/// Plays all the games, one after the other.
+/// Fails if the level can't be loaded.
+pub fn simulate_games(config: &BalanceConfig) -> Result<BalanceReport, String> {
+    let level = match config.level {
+        Some(path) => Some(Level::load(path).map_err(|err| format!("{}: {err}", path.display()))?),
+        None => None,
+    };
+    let max_steps = (config.max_seconds / TIME_STEP) as u32;
+    // The games are played with the tunables the game would use
+    let tunables = Tunables::load();
+
+    let mut seeds = SimRng::new(config.seed);
+    let mut games = Vec::with_capacity(config.games);
+    let mut bricks: Vec<BrickStats> = Vec::new();
+    let mut ball_speeds = BTreeMap::new();
+    let (mut speed_sum, mut speed_samples) = (0.0, 0u64);
+    let mut min_ball_speed = f32::INFINITY;
+    let mut max_ball_speed: f32 = 0.0;
+
+    for _ in 0..config.games {
+        let seed = seeds.next_u64();
+        let mut simulation = Simulation::new(
+            GameMode::Classic,
+            SimRng::new(seed),
+            tunables.clone(),
+            level.clone(),
+        );
+        simulation.hand_over_to_autopilot();
+
+        // Bricks are told apart by where they start out, which is the same in every game
+        let world = simulation.world();
+        let mut brick_indices = Vec::new();
+        for (entity, transform, kind) in world
+            .query_filtered::<(Entity, &Transform, &BrickKind), With<Brick>>()
+            .iter(world)
+        {
+            let position = transform.translation.truncate();
+            let index = match bricks
+                .iter()
+                .position(|brick| Vec2::new(brick.x, brick.y).distance(position) < 0.5)
+            {
+                Some(index) => index,
+                None => {
+                    bricks.push(BrickStats {
+                        x: position.x,
+                        y: position.y,
+                        kind: *kind,
+                        hits: 0,
+                    });
+                    bricks.len() - 1
+                }
+            };
+            brick_indices.push((entity, index));
+        }
+
+        let mut hit_reader = ManualEventReader::<BrickHitEvent>::default();
+        let mut steps = 0;
+        while !simulation.is_over() && steps < max_steps {
+            simulation.step();
+            steps += 1;
+
+            let world = simulation.world();
+            for BrickHitEvent(brick) in hit_reader.iter(world.resource::<Events<BrickHitEvent>>()) {
+                if let Some((_, index)) = brick_indices.iter().find(|(entity, _)| entity == brick) {
+                    bricks[*index].hits += 1;
+                }
+            }
+            for velocity in world.query_filtered::<&Velocity, With<Ball>>().iter(world) {
+                let speed = velocity.0.length();
+                let bucket = ((speed / SPEED_BUCKET).floor() * SPEED_BUCKET) as u32;
+                *ball_speeds.entry(bucket).or_insert(0) += 1;
+                speed_sum += speed;
+                speed_samples += 1;
+                min_ball_speed = min_ball_speed.min(speed);
+                max_ball_speed = max_ball_speed.max(speed);
+            }
+        }
+
+        let timed_out = !simulation.is_over();
+        let world = simulation.world();
+        let scoreboard = world.resource::<Scoreboard>();
+        games.push(GameStats {
+            seed,
+            cleared: *world.resource::<RoundState>() == RoundState::Cleared,
+            timed_out,
+            seconds: steps as f32 * TIME_STEP,
+            lives_lost: STARTING_LIVES.saturating_sub(scoreboard.lives),
+            score: scoreboard.score,
+        });
+    }
+
+    let cleared: Vec<&GameStats> = games.iter().filter(|game| game.cleared).collect();
+    let mean = |sum: f32, count: usize| if count == 0 { 0.0 } else { sum / count as f32 };
+    let summary = Summary {
+        games: games.len(),
+        clear_rate: mean(cleared.len() as f32, games.len()),
+        mean_seconds_to_clear: (!cleared.is_empty())
+            .then(|| mean(cleared.iter().map(|game| game.seconds).sum(), cleared.len())),
+        mean_lives_lost: mean(
+            games.iter().map(|game| game.lives_lost as f32).sum(),
+            games.len(),
+        ),
+        timeouts: games.iter().filter(|game| game.timed_out).count(),
+        min_ball_speed: if speed_samples == 0 {
+            0.0
+        } else {
+            min_ball_speed
+        },
+        mean_ball_speed: if speed_samples == 0 {
+            0.0
+        } else {
+            speed_sum / speed_samples as f32
+        },
+        max_ball_speed,
+    };
+
+    Ok(BalanceReport {
+        summary,
+        games,
+        bricks,
+        ball_speeds,
+    })
+}
+
+impl BalanceReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("the report is always valid JSON")
+    }
+
+    /// A few tables one after the other, separated by blank lines:
+    /// the summary, the ball speeds, the brick hits and the games themselves
+    pub fn to_csv(&self) -> String {
+        let summary = &self.summary;
+        let mut csv = String::from("statistic,value\n");
+        let optional = |value: Option<f32>| value.map_or(String::new(), |value| value.to_string());
+        let rows = [
+            ("games", summary.games.to_string()),
+            ("clear_rate", summary.clear_rate.to_string()),
+            (
+                "mean_seconds_to_clear",
+                optional(summary.mean_seconds_to_clear),
+            ),
+            ("mean_lives_lost", summary.mean_lives_lost.to_string()),
+            ("timeouts", summary.timeouts.to_string()),
+            ("min_ball_speed", summary.min_ball_speed.to_string()),
+            ("mean_ball_speed", summary.mean_ball_speed.to_string()),
+            ("max_ball_speed", summary.max_ball_speed.to_string()),
+        ];
+        for (statistic, value) in rows {
+            let _ = writeln!(csv, "{statistic},{value}");
+        }
+
+        csv.push_str("\nball_speed,steps\n");
+        for (speed, steps) in &self.ball_speeds {
+            let _ = writeln!(csv, "{speed},{steps}");
+        }
+
+        csv.push_str("\nx,y,kind,hits\n");
+        for brick in &self.bricks {
+            let _ = writeln!(
+                csv,
+                "{},{},{:?},{}",
+                brick.x, brick.y, brick.kind, brick.hits
+            );
+        }
+
+        csv.push_str("\nseed,cleared,timed_out,seconds,lives_lost,score\n");
+        for game in &self.games {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                game.seed, game.cleared, game.timed_out, game.seconds, game.lives_lost, game.score
+            );
+        }
+        csv
+    }
+}
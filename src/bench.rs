@@ -0,0 +1,73 @@
+//! Timing code for the benchmarks in `benches`, only built with the `bench` feature.
+
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{broadphase::Broadphase, collision::Collider};
+
+/// How long it takes on average to find what a ball touches among `bricks` bricks packed
+/// into the arena, by testing every brick and with the grid
+pub fn time_lookups(bricks: usize, lookups: usize) -> (Duration, Duration) {
+    assert!(lookups > 0, "There is no average of no lookups");
+
+    // Small bricks, so that thousands of them fit
+    let columns = (bricks as f32).sqrt().ceil() as usize;
+    let brick_size = Vec2::splat(800.0 / columns as f32);
+    let colliders: Vec<(Entity, Transform, Collider)> = (0..bricks)
+        .map(|index| {
+            let cell = Vec2::new((index % columns) as f32, (index / columns) as f32);
+            let position = Vec2::new(-400.0, -300.0) + (cell + 0.5) * brick_size;
+            let transform = Transform::from_translation(position.extend(0.0));
+            let collider = Collider::OrientedBox(brick_size * 0.9);
+            (Entity::from_raw(index as u32), transform, collider)
+        })
+        .collect();
+
+    let mut broadphase = Broadphase::new(brick_size.max_element());
+    for (entity, transform, collider) in &colliders {
+        broadphase.insert(*entity, transform, collider);
+    }
+    let by_entity: HashMap<Entity, (Transform, Collider)> = colliders
+        .iter()
+        .map(|&(entity, transform, collider)| (entity, (transform, collider)))
+        .collect();
+
+    // The ball goes around the arena, the same way for both
+    let radius = 7.5;
+    let ball = |lookup: usize| {
+        let angle = lookup as f32 * 0.37;
+        Vec2::new(angle.cos() * 380.0, angle.sin() * 280.0)
+    };
+
+    let start = Instant::now();
+    let mut hits = 0;
+    for lookup in 0..lookups {
+        let center = ball(lookup);
+        hits += colliders
+            .iter()
+            .filter(|(_, transform, collider)| {
+                collider.contact(transform, center, radius).is_some()
+            })
+            .count();
+    }
+    let every_brick = start.elapsed();
+
+    let start = Instant::now();
+    let mut grid_hits = 0;
+    for lookup in 0..lookups {
+        let center = ball(lookup);
+        grid_hits += broadphase
+            .near(center, radius)
+            .iter()
+            .filter(|entity| {
+                let (transform, collider) = &by_entity[*entity];
+                collider.contact(transform, center, radius).is_some()
+            })
+            .count();
+    }
+    let grid = start.elapsed();
+
+    assert_eq!(hits, grid_hits, "The grid should find the same bricks");
+    (every_brick / lookups as u32, grid / lookups as u32)
+}
@@ -0,0 +1,73 @@
+//! Plays a batch of headless games with the autopilot and prints statistics about them:
+//! `balance [--games N] [--level path] [--seed S] [--max-seconds T] [--format csv|json]`.
+//! Without a level, the usual grid of bricks is played.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use breakout_bevy::{simulate_games, BalanceConfig};
+
+const DEFAULT_GAMES: usize = 100;
+const DEFAULT_MAX_SECONDS: f32 = 600.0;
+
+// The value following `name` on the command line, if any
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+fn parsed_arg<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match arg_value(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid value for {name}: {value}")),
+        None => Ok(default),
+    }
+}
+
+fn main() -> ExitCode {
+    // Some seed is printed either way, so the batch can be run again
+    let time_seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    let config = (|| {
+        Ok::<_, String>((
+            parsed_arg("--games", DEFAULT_GAMES)?,
+            arg_value("--level").map(PathBuf::from),
+            parsed_arg("--seed", time_seed)?,
+            parsed_arg("--max-seconds", DEFAULT_MAX_SECONDS)?,
+        ))
+    })();
+    let (games, level, seed, max_seconds) = match config {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let json = match arg_value("--format").as_deref() {
+        None | Some("csv") => false,
+        Some("json") => true,
+        Some(format) => {
+            eprintln!("error: unknown format {format}, expected csv or json");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    eprintln!("Playing {games} games with seed {seed}");
+    let report = simulate_games(&BalanceConfig {
+        games,
+        level: level.as_deref(),
+        seed,
+        max_seconds,
+    });
+    match report {
+        Ok(report) if json => println!("{}", report.to_json()),
+        Ok(report) => print!("{}", report.to_csv()),
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,51 @@
+//! Turns a pixel art image into a level file:
+//! `import-level <image> <output> [--palette path] [--name name] [--author author]`.
+//! Without a palette, the colors of the game's own bricks are used.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use breakout_bevy::import_pixel_art;
+
+const DEFAULT_PALETTE: &str = "assets/palettes/default.ron";
+const OPTIONS: [&str; 3] = ["--palette", "--name", "--author"];
+
+// The value following `name` on the command line, if any
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+fn main() -> ExitCode {
+    // Everything that isn't an option or an option's value
+    let mut positional = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if OPTIONS.contains(&arg.as_str()) {
+            args.next();
+        } else {
+            positional.push(PathBuf::from(arg));
+        }
+    }
+    let [image, output] = positional.as_slice() else {
+        eprintln!(
+            "usage: import-level <image> <output> [--palette path] [--name name] [--author author]"
+        );
+        return ExitCode::FAILURE;
+    };
+
+    let palette =
+        PathBuf::from(arg_value("--palette").unwrap_or_else(|| DEFAULT_PALETTE.to_string()));
+    let name = arg_value("--name");
+    let author = arg_value("--author");
+    match import_pixel_art(image, &palette, output, name.as_deref(), author.as_deref()) {
+        Ok(bricks) => {
+            println!("Saved {} with {bricks} bricks", output.display());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
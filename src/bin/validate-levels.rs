@@ -0,0 +1,94 @@
+//! Checks level files and packs for mistakes: `validate-levels [paths...]`.
+//! A path can be a level file or a pack's directory, whose manifest and levels are all checked.
+//! Without any paths, every level and pack in `assets/levels` is checked.
+//! Exits with an error code if any level has errors, so it can run before merging changes.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use breakout_bevy::{
+    lint_level_file, lint_pack, LevelProblem, Severity, LEVELS_DIRECTORY, MANIFEST_FILE,
+};
+
+#[derive(Default)]
+struct Tally {
+    errors: usize,
+    warnings: usize,
+    levels: usize,
+    packs: usize,
+}
+
+impl Tally {
+    fn report(&mut self, path: &Path, problems: Vec<LevelProblem>) {
+        for problem in problems {
+            match problem.severity {
+                Severity::Error => self.errors += 1,
+                Severity::Warning => self.warnings += 1,
+            }
+            eprintln!("{}: {problem}", path.display());
+        }
+    }
+
+    fn fail(&mut self, path: &Path, err: String) {
+        eprintln!("{}: error: {err}", path.display());
+        self.errors += 1;
+    }
+}
+
+fn main() -> ExitCode {
+    let mut paths: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if paths.is_empty() {
+        paths = match fs::read_dir(LEVELS_DIRECTORY) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.join(MANIFEST_FILE).is_file()
+                        || path.extension().is_some_and(|extension| extension == "ron")
+                })
+                .collect(),
+            Err(err) => {
+                eprintln!("{LEVELS_DIRECTORY}: error: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        paths.sort();
+    }
+
+    let mut tally = Tally::default();
+    let mut levels = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            levels.push(path);
+            continue;
+        }
+        match lint_pack(&path) {
+            Ok((problems, pack_levels)) => {
+                tally.packs += 1;
+                tally.report(&path, problems);
+                levels.extend(pack_levels);
+            }
+            Err(err) => tally.fail(&path, err),
+        }
+    }
+
+    for path in &levels {
+        tally.levels += 1;
+        match lint_level_file(path) {
+            Ok(problems) => tally.report(path, problems),
+            Err(err) => tally.fail(path, err),
+        }
+    }
+
+    println!(
+        "Checked {} levels in {} packs and on their own: {} errors, {} warnings",
+        tally.levels, tally.packs, tally.errors, tally.warnings
+    );
+    if tally.errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,127 @@
+//! A uniform grid over the arena, keeping track of which colliders are in which cells,
+//! so the ball only has to be tested against the colliders near it rather than all of them.
+//!
+//! Colliders are added to every cell their bounds overlap, and moved over whenever their
+//! `Transform` or `Collider` changes. Bricks the ball breaks are taken out right away,
+//! and any other collider once it is despawned.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{collision::Collider, tunables::Tunables, Ball};
+
+#[derive(Resource)]
+pub(crate) struct Broadphase {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    // The lowest and highest cells each collider is in, to take it out again
+    ranges: HashMap<Entity, (IVec2, IVec2)>,
+}
+
+impl Broadphase {
+    pub(crate) fn new(cell_size: f32) -> Self {
+        // Every point would be in a cell at infinity otherwise
+        assert!(
+            cell_size > 0.0,
+            "The broadphase needs cells of some size, not {cell_size}"
+        );
+        Broadphase {
+            cell_size,
+            cells: HashMap::default(),
+            ranges: HashMap::default(),
+        }
+    }
+
+    fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    fn cells_between(min: IVec2, max: IVec2) -> impl Iterator<Item = IVec2> {
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    /// Adds a collider, or moves it over if it is in already
+    pub(crate) fn insert(&mut self, entity: Entity, transform: &Transform, collider: &Collider) {
+        let (min, max) = collider.bounds(transform);
+        let range = (self.cell(min), self.cell(max));
+        if self.ranges.get(&entity) == Some(&range) {
+            return;
+        }
+        self.remove(entity);
+
+        for cell in Self::cells_between(range.0, range.1) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+        self.ranges.insert(entity, range);
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        let Some((min, max)) = self.ranges.remove(&entity) else {
+            return;
+        };
+        for cell in Self::cells_between(min, max) {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|&other| other != entity);
+                if entities.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    /// The colliders in the cells a circle overlaps, each once
+    pub(crate) fn near(&self, center: Vec2, radius: f32) -> Vec<Entity> {
+        let min = self.cell(center - radius);
+        let max = self.cell(center + radius);
+        let mut entities: Vec<Entity> = Self::cells_between(min, max)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        entities.sort_unstable();
+        entities.dedup();
+        entities
+    }
+}
+
+// Sized after the tunables, which have to be in the world first
+impl FromWorld for Broadphase {
+    fn from_world(world: &mut World) -> Self {
+        Broadphase::new(cell_size(world.resource::<Tunables>()))
+    }
+}
+
+// A brick or so per cell keeps the lists short without putting bricks in too many cells
+pub(crate) fn cell_size(tunables: &Tunables) -> f32 {
+    tunables.brick_step().max_element()
+}
+
+// Runs right before the collisions are checked, once the paddles have moved.
+// Rapier keeps its own, so it isn't run with the `rapier` feature.
+#[cfg_attr(feature = "rapier", allow(dead_code))]
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    tunables: Res<Tunables>,
+    removed: RemovedComponents<Collider>,
+    changed_query: Query<
+        (Entity, &Transform, &Collider),
+        (Without<Ball>, Or<(Changed<Transform>, Changed<Collider>)>),
+    >,
+    collider_query: Query<(Entity, &Transform, &Collider), Without<Ball>>,
+) {
+    // The cells are sized after the bricks, so start over when they change size
+    if tunables.is_changed() && broadphase.cell_size != cell_size(&tunables) {
+        *broadphase = Broadphase::new(cell_size(&tunables));
+        for (entity, transform, collider) in &collider_query {
+            broadphase.insert(entity, transform, collider);
+        }
+        return;
+    }
+
+    for entity in removed.iter() {
+        broadphase.remove(entity);
+    }
+    for (entity, transform, collider) in &changed_query {
+        broadphase.insert(entity, transform, collider);
+    }
+}
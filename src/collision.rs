@@ -0,0 +1,321 @@
+//! The shapes things collide as, and how the ball runs into each of them.
+//!
+//! Only the ball moves on its own, so every test is between a circle and some other shape.
+//! A hit comes with the direction to push the ball out along, which is also what it bounces off,
+//! and how far it got in.
+
+use bevy::prelude::*;
+
+/// Relative to the entity's `Transform`, in the plane the game is played in
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Collider {
+    /// A circle of the given radius
+    Circle(f32),
+    /// A box of the given size, lined up with the axes
+    Aabb(Vec2),
+    /// A box of the given size, turned around the z axis along with its entity
+    OrientedBox(Vec2),
+    /// A wall the ball can only run into from one side
+    Segment(Segment),
+}
+
+/// Where a circle touches a collider
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Contact {
+    /// Points away from the collider, towards the circle's center
+    pub(crate) normal: Vec2,
+    /// How far the circle is into the collider, along the normal
+    pub(crate) depth: f32,
+}
+
+/// A straight line from `start` to `end`, solid only from its left hand side,
+/// so that going around an outline counterclockwise has its inside to the left
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Segment {
+    pub(crate) start: Vec2,
+    pub(crate) end: Vec2,
+}
+
+impl Collider {
+    /// Whether a circle touches the collider of an entity with the given transform
+    pub(crate) fn contact(
+        &self,
+        transform: &Transform,
+        center: Vec2,
+        radius: f32,
+    ) -> Option<Contact> {
+        let position = transform.translation.truncate();
+        match *self {
+            Collider::Circle(other_radius) => circle_circle(center, radius, position, other_radius),
+            Collider::Aabb(size) => circle_box(center, radius, position, size, Vec2::X),
+            Collider::OrientedBox(size) => {
+                // The box's own x axis, once turned
+                let axis = (transform.rotation * Vec3::X)
+                    .truncate()
+                    .normalize_or_zero();
+                circle_box(center, radius, position, size, axis)
+            }
+            Collider::Segment(segment) => {
+                circle_segment(center, radius, segment.moved_by(position))
+            }
+        }
+    }
+}
+
+impl Contact {
+    /// Bounces a circle off what it touched, the way the ball does
+    pub(crate) fn bounce(&self, center: &mut Vec2, velocity: &mut Vec2) {
+        // push it back out, so it doesn't hit the same thing again on the next step
+        *center += self.normal * self.depth;
+
+        // only reflect if it is heading into what it hit, mirroring its velocity
+        // about the contact normal, so corners send it off at an angle
+        let towards = velocity.dot(self.normal);
+        if towards < 0.0 {
+            *velocity -= 2.0 * towards * self.normal;
+        }
+    }
+}
+
+impl Collider {
+    /// The smallest box lined up with the axes around the collider, as its lowest and highest corners
+    pub(crate) fn bounds(&self, transform: &Transform) -> (Vec2, Vec2) {
+        let position = transform.translation.truncate();
+        let half_size = match *self {
+            Collider::Circle(radius) => Vec2::splat(radius),
+            Collider::Aabb(size) => size / 2.0,
+            Collider::OrientedBox(size) => {
+                let axis = (transform.rotation * Vec3::X)
+                    .truncate()
+                    .normalize_or_zero();
+                (axis * size.x / 2.0).abs() + (axis.perp() * size.y / 2.0).abs()
+            }
+            Collider::Segment(segment) => {
+                let start = position + segment.start;
+                let end = position + segment.end;
+                return (start.min(end), start.max(end));
+            }
+        };
+        (position - half_size, position + half_size)
+    }
+}
+
+impl Segment {
+    /// Points towards the side the segment is solid from
+    pub(crate) fn normal(&self) -> Vec2 {
+        (self.end - self.start).perp().normalize_or_zero()
+    }
+
+    fn moved_by(self, offset: Vec2) -> Self {
+        Segment {
+            start: self.start + offset,
+            end: self.end + offset,
+        }
+    }
+
+    // How far along the segment the point closest to `point` is, from 0 at the start to 1 at the end
+    fn closest_fraction(&self, point: Vec2) -> f32 {
+        let along = self.end - self.start;
+        ((point - self.start).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+    }
+
+    /// Time until a ray starting on the solid side crosses the segment,
+    /// once moved `inset` further that way, along with the segment's normal
+    pub(crate) fn cast_ray(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        inset: f32,
+    ) -> Option<(f32, Vec2)> {
+        let normal = self.normal();
+        let towards = direction.dot(normal);
+        // Heading away from the segment, or parallel to it
+        if towards >= 0.0 {
+            return None;
+        }
+
+        let start = self.start + normal * inset;
+        let time = (start - origin).dot(normal) / towards;
+        if time <= 0.0 {
+            return None;
+        }
+        let along = self.end - self.start;
+        let fraction = (origin + direction * time - start).dot(along) / along.length_squared();
+        (0.0..=1.0).contains(&fraction).then_some((time, normal))
+    }
+}
+
+fn circle_circle(
+    center: Vec2,
+    radius: f32,
+    other_center: Vec2,
+    other_radius: f32,
+) -> Option<Contact> {
+    let offset = center - other_center;
+    let distance = offset.length();
+    let depth = radius + other_radius - distance;
+    if depth < 0.0 {
+        return None;
+    }
+    // Right on top of each other, any way out will do
+    let normal = if distance > 0.0 {
+        offset / distance
+    } else {
+        Vec2::Y
+    };
+    Some(Contact { normal, depth })
+}
+
+/// `axis` is the direction the box's width goes along, of unit length
+fn circle_box(
+    center: Vec2,
+    radius: f32,
+    box_center: Vec2,
+    size: Vec2,
+    axis: Vec2,
+) -> Option<Contact> {
+    let half_size = size / 2.0;
+    // In the box's own frame, where it is lined up with the axes
+    let relative = center - box_center;
+    let local = Vec2::new(relative.dot(axis), relative.dot(axis.perp()));
+    let closest = local.clamp(-half_size, half_size);
+    let offset = local - closest;
+
+    let (local_normal, depth) = if offset != Vec2::ZERO {
+        let distance = offset.length();
+        if distance > radius {
+            return None;
+        }
+        // Outside the box, off one of its faces or corners
+        (offset / distance, radius - distance)
+    } else {
+        // The center got inside the box, so push it out through the closest face
+        let to_faces = half_size - local.abs();
+        if to_faces.x < to_faces.y {
+            (Vec2::new(local.x.signum(), 0.0), to_faces.x + radius)
+        } else {
+            (Vec2::new(0.0, local.y.signum()), to_faces.y + radius)
+        }
+    };
+
+    let normal = axis * local_normal.x + axis.perp() * local_normal.y;
+    Some(Contact { normal, depth })
+}
+
+fn circle_segment(center: Vec2, radius: f32, segment: Segment) -> Option<Contact> {
+    let fraction = segment.closest_fraction(center);
+    let offset = center - segment.start.lerp(segment.end, fraction);
+    let distance = offset.length();
+    if distance > radius {
+        return None;
+    }
+
+    // Along the segment, always back out on the solid side,
+    // even if the center got past the line already
+    if (fraction > 0.0 && fraction < 1.0) || distance == 0.0 {
+        let normal = segment.normal();
+        let depth = radius - (center - segment.start).dot(normal);
+        Some(Contact { normal, depth })
+    } else {
+        // Past either end, away from the corner
+        Some(Contact {
+            normal: offset / distance,
+            depth: radius - distance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32, y: f32) -> Transform {
+        Transform::from_xyz(x, y, 0.0)
+    }
+
+    #[test]
+    fn circle_off_a_box_face_is_pushed_straight_back() {
+        let contact = Collider::Aabb(Vec2::new(20.0, 20.0))
+            .contact(&at(100.0, 0.0), Vec2::new(85.0, 0.0), 6.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::NEG_X);
+        assert_eq!(contact.depth, 1.0);
+
+        // Just out of reach
+        assert_eq!(
+            Collider::Aabb(Vec2::new(20.0, 20.0)).contact(
+                &at(100.0, 0.0),
+                Vec2::new(83.0, 0.0),
+                6.0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn circle_off_a_box_corner_is_pushed_away_from_it() {
+        let contact = Collider::Aabb(Vec2::new(20.0, 20.0))
+            .contact(&at(0.0, 0.0), Vec2::new(13.0, 14.0), 6.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::new(0.6, 0.8));
+        assert_eq!(contact.depth, 1.0);
+    }
+
+    #[test]
+    fn center_inside_a_box_leaves_through_the_closest_face() {
+        // Closer to the top face than to the right one
+        let contact = Collider::Aabb(Vec2::new(100.0, 20.0))
+            .contact(&at(0.0, 0.0), Vec2::new(10.0, 5.0), 4.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::Y);
+        assert_eq!(contact.depth, 9.0);
+    }
+
+    #[test]
+    fn turned_box_faces_turn_with_it() {
+        // Its width goes up once turned a quarter of the way around
+        let transform =
+            at(0.0, 0.0).with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        let contact = Collider::OrientedBox(Vec2::new(40.0, 10.0))
+            .contact(&transform, Vec2::new(0.0, 25.0), 6.0)
+            .unwrap();
+        assert!(contact.normal.abs_diff_eq(Vec2::Y, 1e-5), "{contact:?}");
+        assert!((contact.depth - 1.0).abs() < 1e-4, "{contact:?}");
+    }
+
+    #[test]
+    fn coincident_circle_centers_are_pushed_apart_upwards() {
+        let contact = Collider::Circle(2.0)
+            .contact(&at(5.0, 5.0), Vec2::new(5.0, 5.0), 3.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::Y);
+        assert_eq!(contact.depth, 5.0);
+    }
+
+    #[test]
+    fn circle_past_a_segment_end_is_pushed_away_from_it() {
+        let wall = Collider::Segment(Segment {
+            start: Vec2::ZERO,
+            end: Vec2::new(10.0, 0.0),
+        });
+        let contact = wall
+            .contact(&at(0.0, 0.0), Vec2::new(13.0, 4.0), 6.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::new(0.6, 0.8));
+        assert_eq!(contact.depth, 1.0);
+    }
+
+    #[test]
+    fn circle_through_a_segment_backs_out_on_its_solid_side() {
+        // Solid from the left going from start to end, which is up here
+        let wall = Collider::Segment(Segment {
+            start: Vec2::ZERO,
+            end: Vec2::new(10.0, 0.0),
+        });
+        let contact = wall
+            .contact(&at(0.0, 0.0), Vec2::new(5.0, -1.0), 2.0)
+            .unwrap();
+        assert_eq!(contact.normal, Vec2::Y);
+        assert_eq!(contact.depth, 3.0);
+    }
+}
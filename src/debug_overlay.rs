@@ -0,0 +1,221 @@
+//! A debug overlay, toggled with F3: draws the shape of every collider, the velocity of
+//! everything moving, and a marker where the ball hit something, for a few frames after.
+//!
+//! It shows what the game is really testing the ball against, which isn't always what is drawn:
+//! walls, for one, are only solid from one side.
+
+use bevy::prelude::*;
+
+use crate::{
+    collision::Collider,
+    wall::{LineList, LineMaterial},
+    CollisionEvent, Velocity, BALL_RADIUS,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+const COLLIDER_COLOR: Color = Color::rgb(0.2, 1.0, 0.4);
+const VELOCITY_COLOR: Color = Color::rgb(1.0, 0.9, 0.2);
+const CONTACT_COLOR: Color = Color::rgb(1.0, 0.2, 0.6);
+
+// In front of the bricks, paddles and ball, so nothing hides the lines
+const OVERLAY_Z: f32 = BALL_RADIUS;
+// Circles are drawn as polygons with this many sides
+const CIRCLE_SIDES: usize = 16;
+// Walls get a short tick on the side the ball bounces off
+const WALL_TICK_LENGTH: f32 = 8.0;
+// A velocity arrow goes as far as what it belongs to moves in this many seconds
+const VELOCITY_ARROW_SECONDS: f32 = 0.2;
+const ARROW_HEAD_SIZE: f32 = 8.0;
+const CONTACT_MARKER_SIZE: f32 = 6.0;
+// How long a contact stays marked
+const CONTACT_FRAMES: u32 = 20;
+
+#[derive(Resource, Default)]
+pub(crate) struct DebugOverlay {
+    shown: bool,
+    // Where the ball recently hit something, and for how many more frames to mark it
+    contacts: Vec<(Vec2, u32)>,
+}
+
+// Each part of the overlay is a mesh of lines of its own color
+#[derive(Component, Clone, Copy)]
+pub(crate) enum OverlayLayer {
+    Colliders,
+    Velocities,
+    Contacts,
+}
+
+impl OverlayLayer {
+    fn color(self) -> Color {
+        match self {
+            OverlayLayer::Colliders => COLLIDER_COLOR,
+            OverlayLayer::Velocities => VELOCITY_COLOR,
+            OverlayLayer::Contacts => CONTACT_COLOR,
+        }
+    }
+}
+
+pub(crate) fn setup_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    for layer in [
+        OverlayLayer::Colliders,
+        OverlayLayer::Velocities,
+        OverlayLayer::Contacts,
+    ] {
+        commands.spawn((
+            MaterialMeshBundle {
+                // Hidden until there is something to draw
+                mesh: meshes.add(Mesh::from(LineList {
+                    lines: vec![(Vec3::ZERO, Vec3::ZERO)],
+                })),
+                material: materials.add(LineMaterial {
+                    color: layer.color(),
+                }),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            },
+            layer,
+        ));
+    }
+}
+
+// Toggles the overlay, and keeps track of where the ball hits things while it is shown
+pub(crate) fn update_overlay(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    if keyboard_input.just_pressed(TOGGLE_KEY) {
+        overlay.shown = !overlay.shown;
+        overlay.contacts.clear();
+    }
+
+    for event in collision_events.iter() {
+        if overlay.shown {
+            overlay.contacts.push((event.point, CONTACT_FRAMES));
+        }
+    }
+}
+
+// Runs once things have been moved to where they are drawn this frame
+pub(crate) fn draw_overlay(
+    mut overlay: ResMut<DebugOverlay>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut layer_query: Query<(&OverlayLayer, &Handle<Mesh>, &mut Visibility)>,
+    collider_query: Query<(&Transform, &Collider)>,
+    velocity_query: Query<(&Transform, &Velocity)>,
+) {
+    for contact in &mut overlay.contacts {
+        contact.1 = contact.1.saturating_sub(1);
+    }
+    overlay.contacts.retain(|&(_, frames)| frames > 0);
+
+    for (layer, mesh, mut visibility) in &mut layer_query {
+        let lines: Vec<(Vec2, Vec2)> = if !overlay.shown {
+            Vec::new()
+        } else {
+            match layer {
+                OverlayLayer::Colliders => collider_query
+                    .iter()
+                    .flat_map(|(transform, collider)| collider_lines(transform, collider))
+                    .collect(),
+                OverlayLayer::Velocities => velocity_query
+                    .iter()
+                    .flat_map(|(transform, velocity)| {
+                        arrow_lines(transform.translation.truncate(), velocity.0)
+                    })
+                    .collect(),
+                OverlayLayer::Contacts => overlay
+                    .contacts
+                    .iter()
+                    .flat_map(|&(point, _)| contact_lines(point))
+                    .collect(),
+            }
+        };
+
+        visibility.is_visible = !lines.is_empty();
+        if lines.is_empty() {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = Mesh::from(LineList {
+                lines: lines
+                    .into_iter()
+                    .map(|(start, end)| (start.extend(OVERLAY_Z), end.extend(OVERLAY_Z)))
+                    .collect(),
+            });
+        }
+    }
+}
+
+fn collider_lines(transform: &Transform, collider: &Collider) -> Vec<(Vec2, Vec2)> {
+    let position = transform.translation.truncate();
+    let box_lines = |size: Vec2, axis: Vec2| -> Vec<(Vec2, Vec2)> {
+        let half_width = axis * size.x / 2.0;
+        let half_height = axis.perp() * size.y / 2.0;
+        let corners = [
+            position - half_width - half_height,
+            position + half_width - half_height,
+            position + half_width + half_height,
+            position - half_width + half_height,
+        ];
+        (0..corners.len())
+            .map(|index| (corners[index], corners[(index + 1) % corners.len()]))
+            .collect()
+    };
+
+    match *collider {
+        Collider::Circle(radius) => (0..CIRCLE_SIDES)
+            .map(|side| {
+                let point = |side: usize| {
+                    let angle = side as f32 / CIRCLE_SIDES as f32 * std::f32::consts::TAU;
+                    position + Vec2::new(angle.cos(), angle.sin()) * radius
+                };
+                (point(side), point(side + 1))
+            })
+            .collect(),
+        Collider::Aabb(size) => box_lines(size, Vec2::X),
+        Collider::OrientedBox(size) => {
+            let axis = (transform.rotation * Vec3::X)
+                .truncate()
+                .normalize_or_zero();
+            box_lines(size, axis)
+        }
+        Collider::Segment(segment) => {
+            let start = position + segment.start;
+            let end = position + segment.end;
+            let middle = (start + end) / 2.0;
+            vec![
+                (start, end),
+                (middle, middle + segment.normal() * WALL_TICK_LENGTH),
+            ]
+        }
+    }
+}
+
+fn arrow_lines(start: Vec2, velocity: Vec2) -> Vec<(Vec2, Vec2)> {
+    let tip = start + velocity * VELOCITY_ARROW_SECONDS;
+    let direction = velocity.normalize_or_zero();
+    if direction == Vec2::ZERO {
+        return Vec::new();
+    }
+    let back = tip - direction * ARROW_HEAD_SIZE;
+    let side = direction.perp() * ARROW_HEAD_SIZE / 2.0;
+    vec![(start, tip), (tip, back + side), (tip, back - side)]
+}
+
+// A small cross
+fn contact_lines(point: Vec2) -> Vec<(Vec2, Vec2)> {
+    let size = CONTACT_MARKER_SIZE;
+    vec![
+        (point - Vec2::splat(size), point + Vec2::splat(size)),
+        (
+            point + Vec2::new(-size, size),
+            point + Vec2::new(size, -size),
+        ),
+    ]
+}
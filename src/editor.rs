@@ -0,0 +1,355 @@
+//! The level editor, started with `--editor [path]`.
+//!
+//! Clicking on the arena places a brick on the grid, or cycles through the kinds of brick
+//! if there already is one. Right clicking removes it again.
+//! Tab picks the level's name or author to type in, or some text to spell out in bricks
+//! with Enter. Otherwise, Enter test plays the level (and goes back to editing afterwards)
+//! and Ctrl+S saves it.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::{
+    arena::Arena,
+    level::{BrickKind, Level, LevelBrick},
+    text_bricks,
+    tunables::Tunables,
+    Ball, Brick, BrickAssets, BrickLayout, GameMode, GameOverText, PaddleFilter, PaddleLane,
+    RoundState, Scoreboard, Velocity, SCOREBOARD_FONT_SIZE, SCOREBOARD_TEXT_PADDING,
+    STARTING_LIVES,
+};
+
+const DEFAULT_LEVEL_PATH: &str = "assets/levels/custom.ron";
+const EDITOR_FONT_SIZE: f32 = SCOREBOARD_FONT_SIZE / 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum MetadataField {
+    Name,
+    Author,
+    // Not the level's, but replaces its bricks, see `text_bricks`
+    Text,
+}
+
+#[derive(Resource)]
+pub(crate) struct Editor {
+    path: PathBuf,
+    // What is being typed into, if anything
+    field: Option<MetadataField>,
+    // The text last spelled out in bricks
+    text: String,
+    // The outcome of the last save
+    status: String,
+}
+
+impl Editor {
+    /// The editor, if it was asked for on the command line, along with the level to edit.
+    /// A level that doesn't exist yet starts out as the usual grid of bricks.
+    pub(crate) fn from_args(tunables: &Tunables) -> Option<(Self, Level)> {
+        let mut args = std::env::args().skip_while(|arg| arg != "--editor");
+        args.next()?;
+        let path = PathBuf::from(
+            args.next()
+                .filter(|arg| !arg.starts_with("--"))
+                .unwrap_or_else(|| DEFAULT_LEVEL_PATH.to_string()),
+        );
+
+        let (level, status) = if path.exists() {
+            match Level::load(&path) {
+                Ok(level) => (level, format!("Loaded {}", path.display())),
+                Err(err) => panic!("Could not load the level {}: {err}", path.display()),
+            }
+        } else {
+            (
+                Level::grid(tunables),
+                format!("New level, to be saved to {}", path.display()),
+            )
+        };
+
+        let editor = Editor {
+            path,
+            field: None,
+            text: String::new(),
+            status,
+        };
+        Some((editor, level))
+    }
+}
+
+// Shows the level's metadata and the controls
+#[derive(Component)]
+pub(crate) struct EditorText;
+
+pub(crate) fn setup_editor(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    tunables: Res<Tunables>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: EDITOR_FONT_SIZE,
+        color: tunables.colors.text,
+    };
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::from_style(TextStyle {
+                color: tunables.colors.score,
+                ..style.clone()
+            }),
+            TextSection::from_style(style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: SCOREBOARD_TEXT_PADDING,
+                left: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+            ..default()
+        }),
+        EditorText,
+    ));
+}
+
+pub(crate) fn place_bricks(
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    round: Res<RoundState>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut level: ResMut<Level>,
+) {
+    if *round != RoundState::Editing {
+        return;
+    }
+    let place = mouse_input.just_pressed(MouseButton::Left);
+    let remove = mouse_input.just_pressed(MouseButton::Right);
+    if !place && !remove {
+        return;
+    }
+
+    let Some(cursor) = cursor_in_arena(&windows, &camera_query) else {
+        return;
+    };
+    let position = snap_to_grid(cursor, &tunables, &arena);
+    if !arena.contains(position) {
+        return;
+    }
+
+    if remove {
+        level.bricks.retain(|brick| !brick.is_at(position));
+    } else if let Some(brick) = level.brick_at(position) {
+        brick.kind = brick.kind.next();
+    } else {
+        level.bricks.push(LevelBrick {
+            position: (position.x, position.y),
+            kind: BrickKind::Normal,
+        });
+    }
+}
+
+// Where the mouse cursor points at on the plane the bricks sit in
+fn cursor_in_arena(
+    windows: &Windows,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_primary()?.cursor_position()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    let ray = camera.viewport_to_world(camera_transform, cursor)?;
+
+    let distance = -ray.origin.z / ray.direction.z;
+    (distance > 0.0).then(|| (ray.origin + ray.direction * distance).truncate())
+}
+
+// The grid is the one the usual layout is built on, extended across the whole arena
+fn snap_to_grid(position: Vec2, tunables: &Tunables, arena: &Arena) -> Vec2 {
+    let BrickLayout { origin, step, .. } = BrickLayout::new(tunables, arena);
+    origin + ((position - origin) / step).round() * step
+}
+
+// Keeps the bricks on screen in line with the level being edited
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn show_level(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    round: Res<RoundState>,
+    tunables: Res<Tunables>,
+    level: Res<Level>,
+    brick_query: Query<Entity, With<Brick>>,
+) {
+    if *round != RoundState::Editing || !(level.is_changed() || round.is_changed()) {
+        return;
+    }
+
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    level.spawn_bricks(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut brick_assets,
+        &tunables,
+    );
+}
+
+pub(crate) fn edit_metadata(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    round: Res<RoundState>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut editor: ResMut<Editor>,
+    mut level: ResMut<Level>,
+) {
+    if *round != RoundState::Editing {
+        characters.clear();
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        editor.field = match editor.field {
+            None => Some(MetadataField::Name),
+            Some(MetadataField::Name) => Some(MetadataField::Author),
+            Some(MetadataField::Author) => Some(MetadataField::Text),
+            Some(MetadataField::Text) => None,
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        if editor.field == Some(MetadataField::Text) {
+            let font = Path::new(text_bricks::DEFAULT_FONT);
+            editor.status = match text_bricks::text_bricks(&editor.text, font, &tunables, &arena) {
+                Ok(bricks) => {
+                    level.bricks = bricks;
+                    format!("Spelled out {:?}", editor.text)
+                }
+                Err(err) => format!("Could not spell out {:?}: {err}", editor.text),
+            };
+        }
+        editor.field = None;
+    }
+
+    let Some(field) = editor.field else {
+        characters.clear();
+        return;
+    };
+    let typed: String = characters
+        .iter()
+        .map(|event| event.char)
+        .filter(|char| !char.is_control())
+        .collect();
+    let erase = keyboard_input.just_pressed(KeyCode::Back);
+    if typed.is_empty() && !erase {
+        return;
+    }
+
+    let text = match field {
+        MetadataField::Name => &mut level.name,
+        MetadataField::Author => &mut level.author,
+        MetadataField::Text => &mut editor.text,
+    };
+    if erase {
+        text.pop();
+    }
+    text.push_str(&typed);
+}
+
+// Enter switches between editing the level and playing it, starting the round over each time
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn toggle_test_play(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Res<Editor>,
+    mode: Res<GameMode>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut round: ResMut<RoundState>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut paddle_query: Query<(&mut Transform, &PaddleLane), PaddleFilter>,
+    game_over_query: Query<Entity, With<GameOverText>>,
+) {
+    // Enter also ends typing in the level's name or author
+    if !keyboard_input.just_pressed(KeyCode::Return) || editor.field.is_some() {
+        return;
+    }
+
+    for (mut transform, mut velocity) in &mut ball_query {
+        transform.translation = mode.ball_starting_position(&tunables, &arena);
+        velocity.0 = tunables.serve_velocity();
+    }
+    for (mut transform, lane) in &mut paddle_query {
+        transform.translation.x = lane.center();
+    }
+    scoreboard.score = 0;
+    scoreboard.lives = STARTING_LIVES;
+    for text in &game_over_query {
+        commands.entity(text).despawn();
+    }
+
+    // The bricks on screen already are the level's while editing,
+    // and `show_level` puts them back once done playing
+    *round = if *round == RoundState::Editing {
+        RoundState::Playing
+    } else {
+        RoundState::Editing
+    };
+}
+
+pub(crate) fn save_level(
+    keyboard_input: Res<Input<KeyCode>>,
+    level: Res<Level>,
+    mut editor: ResMut<Editor>,
+) {
+    let control = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    if !control || !keyboard_input.just_pressed(KeyCode::S) {
+        return;
+    }
+
+    editor.status = match level.save(&editor.path) {
+        Ok(()) => format!("Saved to {}", editor.path.display()),
+        Err(err) => format!("Could not save to {}: {err}", editor.path.display()),
+    };
+    info!("{}", editor.status);
+}
+
+pub(crate) fn update_editor_text(
+    round: Res<RoundState>,
+    editor: Res<Editor>,
+    level: Res<Level>,
+    mut query: Query<&mut Text, With<EditorText>>,
+) {
+    if !(round.is_changed() || editor.is_changed() || level.is_changed()) {
+        return;
+    }
+
+    let marker = |field| if editor.field == Some(field) { "_" } else { "" };
+    let mut title = format!(
+        "{}{} by {}{}\n",
+        level.name,
+        marker(MetadataField::Name),
+        level.author,
+        marker(MetadataField::Author),
+    );
+    if editor.field == Some(MetadataField::Text) {
+        title.push_str(&format!("Text: {}_   Enter: spell it out\n", editor.text));
+    }
+    let controls = if *round == RoundState::Editing {
+        format!(
+            "Click: place / cycle brick   Right click: remove   Tab: name / author / text   \
+             Enter: test play   Ctrl+S: save\n{}",
+            editor.status
+        )
+    } else {
+        "Enter: back to editing".to_string()
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = title.clone();
+        text.sections[1].value = controls.clone();
+    }
+}
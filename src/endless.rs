@@ -0,0 +1,91 @@
+//! Endless mode: new rows of bricks keep dropping in from the top,
+//! and the game is over once they reach the paddle
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena, spawn_brick, tunables::Tunables, Brick, BrickAssets, BrickKind, BrickLayout,
+    PaddleHitEvent, RoundState, Scoreboard, TIME_STEP,
+};
+
+// A new row is added after this many seconds or paddle hits, whichever comes first
+const ROW_INTERVAL_SECONDS: f32 = 10.0;
+const ROW_INTERVAL_PADDLE_HITS: usize = 6;
+
+#[derive(Resource, Clone)]
+pub(crate) struct EndlessState {
+    row_timer: Timer,
+    paddle_hits: usize,
+    // How long the player has lasted so far
+    survived: Duration,
+}
+
+impl Default for EndlessState {
+    fn default() -> Self {
+        EndlessState {
+            row_timer: Timer::from_seconds(ROW_INTERVAL_SECONDS, TimerMode::Repeating),
+            paddle_hits: 0,
+            survived: Duration::ZERO,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn descend_bricks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut state: ResMut<EndlessState>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut round: ResMut<RoundState>,
+    mut paddle_hit_events: EventReader<PaddleHitEvent>,
+    mut brick_query: Query<&mut Transform, With<Brick>>,
+) {
+    let step = Duration::from_secs_f32(TIME_STEP);
+    state.survived += step;
+    state.row_timer.tick(step);
+    state.paddle_hits += paddle_hit_events.iter().count();
+
+    // In endless mode the score is the number of seconds survived,
+    // which replaces the points handed out for breaking bricks
+    scoreboard.score = state.survived.as_secs() as usize;
+
+    if !state.row_timer.just_finished() && state.paddle_hits < ROW_INTERVAL_PADDLE_HITS {
+        return;
+    }
+    state.row_timer.reset();
+    state.paddle_hits = 0;
+
+    // Push every brick down by one row...
+    let layout = BrickLayout::new(&tunables, &arena);
+    for mut transform in &mut brick_query {
+        transform.translation.y -= layout.step.y;
+    }
+
+    // ...and fill the freed up top row with fresh bricks
+    let top_row = layout.n_rows - 1;
+    for column in 0..layout.n_columns {
+        spawn_brick(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut brick_assets,
+            &tunables,
+            layout.position(top_row, column),
+            BrickKind::Normal,
+        );
+    }
+
+    // The game is lost as soon as a brick reaches the paddle line
+    let reached_paddle = brick_query
+        .iter()
+        .any(|transform| transform.translation.y - tunables.brick_size.y / 2.0 <= arena.paddle_y());
+    if reached_paddle {
+        *round = RoundState::GameOver;
+    }
+}
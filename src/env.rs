@@ -0,0 +1,166 @@
+//! A reinforcement learning environment, shaped like a gym environment,
+//! for training agents on the classic rules of the game.
+//!
+//! ```no_run
+//! use breakout_bevy::BreakoutEnv;
+//!
+//! let mut env = BreakoutEnv::new();
+//! let mut observation = env.reset(42);
+//! loop {
+//!     // Chase the ball
+//!     let action = (observation[0] - observation[4]).signum();
+//!     let step = env.step(action);
+//!     observation = step.observation;
+//!     if step.done {
+//!         break;
+//!     }
+//! }
+//! ```
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena, headless::Simulation, rng::SimRng, tunables::Tunables, Ball, Brick, BrickLayout,
+    GameMode, Paddle, Player, Scoreboard, Velocity, STARTING_LIVES,
+};
+
+// Taken off the reward for every life lost, in points
+const LIFE_LOST_PENALTY: f32 = 5.0;
+
+// Ball position and velocity, paddle position and lives, followed by the brick grid
+const FIXED_OBSERVATIONS: usize = 6;
+
+/// The outcome of a single step
+#[derive(Clone, Debug)]
+pub struct StepResult {
+    pub observation: Vec<f32>,
+    /// Points scored in the step, minus a penalty for each life lost
+    pub reward: f32,
+    /// Whether the round is over, won or lost. Call [`BreakoutEnv::reset`] to start a new one.
+    pub done: bool,
+}
+
+/// Plays the classic rules headlessly, one fixed step at a time.
+///
+/// Observations always have [`BreakoutEnv::observation_size`] values:
+///
+/// - the ball's position, as `x` and `y` between -1 and 1 across the arena
+/// - the ball's velocity, as `x` and `y` in multiples of its starting speed
+/// - the paddle's position, as `x` between -1 and 1 across the arena
+/// - the lives left, between 0 and 1
+/// - one value for every spot of the brick grid, row by row from the bottom left:
+///   1 if the brick is still there, 0 once it has been broken
+pub struct BreakoutEnv {
+    simulation: Simulation,
+    // Always the defaults, so agents train on the same game whatever the tunables file says
+    tunables: Tunables,
+    layout: BrickLayout,
+    score: usize,
+    lives: usize,
+}
+
+impl BreakoutEnv {
+    pub fn new() -> Self {
+        let tunables = Tunables::default();
+        BreakoutEnv {
+            simulation: Simulation::new(GameMode::Classic, SimRng::new(0), tunables.clone(), None),
+            layout: BrickLayout::new(&tunables, &Arena::default()),
+            tunables,
+            score: 0,
+            lives: STARTING_LIVES,
+        }
+    }
+
+    pub fn observation_size(&self) -> usize {
+        FIXED_OBSERVATIONS + self.layout.n_columns * self.layout.n_rows
+    }
+
+    /// Starts a new round and returns the first observation.
+    ///
+    /// The seed feeds the game's random number generator.
+    /// The classic rules have no randomness of their own, so every round starts the same way.
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.simulation = Simulation::new(
+            GameMode::Classic,
+            SimRng::new(seed),
+            self.tunables.clone(),
+            None,
+        );
+        self.score = 0;
+        self.lives = STARTING_LIVES;
+        self.observe()
+    }
+
+    /// Moves the paddle for one step, from -1 (full speed left) to 1 (full speed right)
+    pub fn step(&mut self, action: f32) -> StepResult {
+        self.simulation.set_paddle_input(Player::One, action);
+        self.simulation.step();
+
+        let scoreboard = self.simulation.world().resource::<Scoreboard>();
+        let (score, lives) = (scoreboard.score, scoreboard.lives);
+        let points = score.saturating_sub(self.score) as f32;
+        let lives_lost = self.lives.saturating_sub(lives) as f32;
+        self.score = score;
+        self.lives = lives;
+
+        StepResult {
+            observation: self.observe(),
+            reward: points - LIFE_LOST_PENALTY * lives_lost,
+            done: self.simulation.is_over(),
+        }
+    }
+
+    fn observe(&mut self) -> Vec<f32> {
+        let mut observation = vec![0.0; self.observation_size()];
+        let world = self.simulation.world();
+
+        // Without a level, the arena always has the default size
+        let arena = Arena::default();
+        let half_size = arena.size() / 2.0;
+        let center = Vec2::new(arena.right() + arena.left(), arena.top() + arena.bottom()) / 2.0;
+        let to_arena = |position: Vec2| (position - center) / half_size;
+
+        if let Some((transform, velocity)) = world
+            .query_filtered::<(&Transform, &Velocity), With<Ball>>()
+            .iter(world)
+            .next()
+        {
+            let position = to_arena(transform.translation.truncate());
+            let velocity = velocity.0 / self.tunables.ball_speed;
+            observation[0..4].copy_from_slice(&[position.x, position.y, velocity.x, velocity.y]);
+        }
+
+        if let Some(transform) = world
+            .query_filtered::<&Transform, With<Paddle>>()
+            .iter(world)
+            .next()
+        {
+            observation[4] = to_arena(transform.translation.truncate()).x;
+        }
+
+        observation[5] = world.resource::<Scoreboard>().lives as f32 / STARTING_LIVES as f32;
+
+        for transform in world
+            .query_filtered::<&Transform, With<Brick>>()
+            .iter(world)
+        {
+            let cell = (transform.translation.truncate() - self.layout.origin) / self.layout.step;
+            let (column, row) = (cell.x.round(), cell.y.round());
+            if column < 0.0 || row < 0.0 {
+                continue;
+            }
+            let (column, row) = (column as usize, row as usize);
+            if column < self.layout.n_columns && row < self.layout.n_rows {
+                observation[FIXED_OBSERVATIONS + row * self.layout.n_columns + column] = 1.0;
+            }
+        }
+
+        observation
+    }
+}
+
+impl Default for BreakoutEnv {
+    fn default() -> Self {
+        BreakoutEnv::new()
+    }
+}
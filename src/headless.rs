@@ -0,0 +1,95 @@
+//! The game without a window, stepped by hand as fast as the computer allows.
+//! Nothing is drawn or played, but the rules are the exact same as in the real game.
+
+use bevy::{asset::AssetPlugin, prelude::*};
+
+use crate::{
+    add_game_rules, autopilot::Autopilot, level::Level, rng::SimRng, run_game_step,
+    set_paddle_input, tunables::Tunables, wall::LineMaterial, GameMode, Paddle, Player,
+    PlayerControlled, RoundState,
+};
+
+pub(crate) struct Simulation {
+    app: App,
+}
+
+impl Simulation {
+    /// A new round, on the given level or on the usual grid of bricks
+    pub(crate) fn new(
+        mode: GameMode,
+        rng: SimRng,
+        tunables: Tunables,
+        level: Option<Level>,
+    ) -> Self {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            // The entities are spawned the same as usual, meshes and all
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_asset::<LineMaterial>()
+            // Never pressed, but `keyboard_paddle_input` still wants to look
+            .init_resource::<Input<KeyCode>>()
+            // Read once, the file isn't watched here
+            .insert_resource(tunables);
+        if let Some(level) = level {
+            app.insert_resource(level);
+        }
+        add_game_rules(&mut app, mode, rng, false);
+
+        // Runs the startup systems
+        app.update();
+
+        // The paddles only move when told to
+        let paddles: Vec<Entity> = app
+            .world
+            .query_filtered::<Entity, With<Paddle>>()
+            .iter(&app.world)
+            .collect();
+        for paddle in paddles {
+            app.world.entity_mut(paddle).remove::<PlayerControlled>();
+        }
+
+        Simulation { app }
+    }
+
+    // Let the computer play instead
+    pub(crate) fn hand_over_to_autopilot(&mut self) {
+        let paddles: Vec<Entity> = self
+            .app
+            .world
+            .query_filtered::<Entity, With<Paddle>>()
+            .iter(&self.app.world)
+            .collect();
+        for paddle in paddles {
+            self.app
+                .world
+                .entity_mut(paddle)
+                .insert(Autopilot::default());
+        }
+    }
+
+    pub(crate) fn world(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+
+    /// Whether the round has ended, won or lost
+    pub(crate) fn is_over(&self) -> bool {
+        *self.app.world.resource::<RoundState>() != RoundState::Playing
+    }
+
+    /// Sets the input of a paddle, from -1 (full speed left) to 1 (full speed right)
+    pub(crate) fn set_paddle_input(&mut self, player: Player, input: f32) {
+        set_paddle_input(&mut self.app.world, player, input);
+    }
+
+    /// Takes a single fixed step, unless the round is already over
+    pub(crate) fn step(&mut self) {
+        if self.is_over() {
+            return;
+        }
+        run_game_step(&mut self.app.world);
+        // Lets the events of this step go, among other bookkeeping
+        self.app.update();
+    }
+}
@@ -0,0 +1,98 @@
+//! Smooths out how things move on screen between the fixed steps of the game.
+//!
+//! Frames don't line up with steps: at high refresh rates, some frames take no step and others two,
+//! which makes the ball stutter if it is drawn right where the last step left it.
+//! Instead, things that move are drawn part of the way between where the last two steps left them,
+//! by how far the time is into the next step.
+//!
+//! Where the steps left something is kept apart, and put back into its `Transform` before the next
+//! steps run, so the game itself only ever sees where things really are.
+
+use bevy::{prelude::*, time::FixedTimesteps};
+
+/// Names the fixed timestep the game steps in, to find how far into the next step the time is
+pub(crate) const FIXED_STEP_LABEL: &str = "game_step";
+
+/// Something that moves during the game's steps, drawn in between where the last two left it
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+    // Where it was last drawn, to tell when something other than a step moved it
+    shown: Vec3,
+}
+
+impl Interpolated {
+    pub(crate) fn new(translation: Vec3) -> Self {
+        Interpolated {
+            previous: translation,
+            current: translation,
+            shown: translation,
+        }
+    }
+
+    // Moved outside of a step, e.g. by loading a save, so there is nothing to move in between
+    fn teleport(&mut self, translation: Vec3) {
+        *self = Interpolated::new(translation);
+    }
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct Interpolation {
+    // Whether a step was taken since things were last drawn
+    stepped: bool,
+    // How far in between the last two steps things were last drawn, from 0.0 to 1.0
+    fraction: f32,
+}
+
+// Puts things back where the last step left them, before the next steps run
+pub(crate) fn restore_simulated_positions(mut query: Query<(&mut Transform, &mut Interpolated)>) {
+    for (mut transform, mut interpolated) in &mut query {
+        if transform.translation != interpolated.shown {
+            interpolated.teleport(transform.translation);
+        }
+        transform.translation = interpolated.current;
+        interpolated.shown = interpolated.current;
+    }
+}
+
+/// Remembers where a step left things. Called at the end of every step, wherever it is taken.
+pub(crate) fn record_step(world: &mut World) {
+    let mut query = world.query::<(&Transform, &mut Interpolated)>();
+    for (transform, mut interpolated) in query.iter_mut(world) {
+        interpolated.previous = interpolated.current;
+        interpolated.current = transform.translation;
+        interpolated.shown = transform.translation;
+    }
+    world.resource_mut::<Interpolation>().stepped = true;
+}
+
+// Runs last thing before drawing, once the steps of the frame are done
+pub(crate) fn interpolate_positions(
+    mut interpolation: ResMut<Interpolation>,
+    fixed_timesteps: Res<FixedTimesteps>,
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+) {
+    let overstep = fixed_timesteps
+        .get(FIXED_STEP_LABEL)
+        .map_or(1.0, |state| state.overstep_percentage() as f32);
+    // The time keeps going while the game waits, e.g. when paused or for the other player's input,
+    // so once it catches up with the last step, things stay there until the next one
+    interpolation.fraction = if interpolation.stepped || overstep >= interpolation.fraction {
+        overstep.min(1.0)
+    } else {
+        1.0
+    };
+    interpolation.stepped = false;
+
+    for (mut transform, mut interpolated) in &mut query {
+        if transform.translation != interpolated.shown {
+            interpolated.teleport(transform.translation);
+            continue;
+        }
+        transform.translation = interpolated
+            .previous
+            .lerp(interpolated.current, interpolation.fraction);
+        interpolated.shown = transform.translation;
+    }
+}
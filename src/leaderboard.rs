@@ -0,0 +1,98 @@
+//! High scores, kept separately for every game mode
+
+use std::fs;
+
+use bevy::prelude::*;
+
+use crate::{editor::Editor, GameMode, RoundState, Scoreboard};
+
+// Plain text, one `<category> <score>` entry per line
+const LEADERBOARD_PATH: &str = "leaderboard.txt";
+// Only the best scores of each category are kept
+const ENTRIES_PER_CATEGORY: usize = 10;
+
+#[derive(Resource, Default, Debug)]
+pub(crate) struct Leaderboard {
+    entries: Vec<(String, usize)>,
+}
+
+impl Leaderboard {
+    /// Reads the leaderboard from disk, starting empty if there is none yet
+    pub(crate) fn load() -> Self {
+        let Ok(contents) = fs::read_to_string(LEADERBOARD_PATH) else {
+            return Leaderboard::default();
+        };
+
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let (category, score) = line.rsplit_once(' ')?;
+                Some((category.to_string(), score.parse().ok()?))
+            })
+            .collect();
+
+        Leaderboard { entries }
+    }
+
+    fn save(&self) {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(category, score)| format!("{category} {score}\n"))
+            .collect();
+
+        if let Err(err) = fs::write(LEADERBOARD_PATH, contents) {
+            warn!("Could not save the leaderboard to {LEADERBOARD_PATH}: {err}");
+        }
+    }
+
+    /// Scores of a category, best first
+    pub(crate) fn scores(&self, category: &str) -> Vec<usize> {
+        let mut scores: Vec<usize> = self
+            .entries
+            .iter()
+            .filter(|(entry_category, _)| entry_category == category)
+            .map(|(_, score)| *score)
+            .collect();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        scores
+    }
+
+    pub(crate) fn best(&self, category: &str) -> Option<usize> {
+        self.scores(category).first().copied()
+    }
+
+    /// Adds a score, dropping whatever no longer makes the cut for its category
+    pub(crate) fn submit(&mut self, category: &str, score: usize) {
+        let mut kept = self.scores(category);
+        kept.push(score);
+        kept.sort_unstable_by(|a, b| b.cmp(a));
+        kept.truncate(ENTRIES_PER_CATEGORY);
+
+        self.entries
+            .retain(|(entry_category, _)| entry_category != category);
+        self.entries
+            .extend(kept.into_iter().map(|score| (category.to_string(), score)));
+    }
+}
+
+// Record the final score of the round under the current mode
+pub(crate) fn record_high_score(
+    round: Res<RoundState>,
+    mode: Res<GameMode>,
+    scoreboard: Res<Scoreboard>,
+    editor: Option<Res<Editor>>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    // A versus match is won or lost, there is no score to rank
+    if !round.is_changed() || *round == RoundState::Playing || *mode == GameMode::Versus {
+        return;
+    }
+    // Neither do test plays in the editor count
+    if matches!(*round, RoundState::Editing | RoundState::SelectingPack) || editor.is_some() {
+        return;
+    }
+
+    leaderboard.submit(mode.name(), scoreboard.score);
+    leaderboard.save();
+}
@@ -0,0 +1,268 @@
+//! Levels: hand-made brick layouts, stored as RON files.
+//!
+//! ```ron
+//! (
+//!     schema: 4,
+//!     name: "Checkers",
+//!     author: "Someone",
+//!     arena: Some((width: 600.0, height: 700.0)),
+//!     walls: Some([(-300.0, -350.0), (300.0, -350.0), (300.0, 250.0), (0.0, 350.0), (-300.0, 250.0)]),
+//!     bricks: [
+//!         (position: (-200.0, 120.0), kind: Normal),
+//!         (position: (-80.0, 120.0), kind: Tough),
+//!     ],
+//! )
+//! ```
+//!
+//! Brick positions are the centers of the bricks, in `Transform` units.
+//! The arena is optional, and the usual one is played in without it.
+//! So are the walls, a closed outline going around the arena, which are otherwise
+//! the four sides of the arena. The walls facing up more than sideways are the floor,
+//! and the ball is lost when it touches them; those facing down are the ceiling.
+//!
+//! The `schema` tells which version of the format a file uses. Files in older formats
+//! are migrated when loaded, and always saved in the current one.
+
+use std::{fmt, fs, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::Arena, spawn_brick, tunables::Tunables, wall::WallOutline, BrickAssets, BrickLayout,
+};
+
+/// The version of the format levels are saved in
+pub(crate) const LEVEL_SCHEMA: u32 = 4;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+pub(crate) enum BrickKind {
+    #[default]
+    Normal,
+    // Takes two hits, turning into a normal brick after the first one
+    Tough,
+    // Only ever bounces the ball back
+    Unbreakable,
+}
+
+impl BrickKind {
+    pub(crate) const ALL: [BrickKind; 3] =
+        [BrickKind::Normal, BrickKind::Tough, BrickKind::Unbreakable];
+
+    pub(crate) fn breakable(self) -> bool {
+        self != BrickKind::Unbreakable
+    }
+
+    pub(crate) fn color(self, tunables: &Tunables) -> Color {
+        match self {
+            BrickKind::Normal => tunables.colors.brick,
+            BrickKind::Tough => tunables.colors.tough_brick,
+            BrickKind::Unbreakable => tunables.colors.unbreakable_brick,
+        }
+    }
+
+    /// The kind after this one, wrapping around, for cycling through them in the editor
+    pub(crate) fn next(self) -> Self {
+        let index = BrickKind::ALL
+            .iter()
+            .position(|&kind| kind == self)
+            .unwrap_or(0);
+        BrickKind::ALL[(index + 1) % BrickKind::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct LevelBrick {
+    pub(crate) position: (f32, f32),
+    #[serde(default)]
+    pub(crate) kind: BrickKind,
+}
+
+impl LevelBrick {
+    pub(crate) fn position(&self) -> Vec2 {
+        Vec2::new(self.position.0, self.position.1)
+    }
+
+    pub(crate) fn is_at(&self, position: Vec2) -> bool {
+        self.position().distance(position) < 0.5
+    }
+}
+
+/// The level being played, when it comes from a file rather than the usual grid of bricks
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct Level {
+    pub(crate) schema: u32,
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) author: String,
+    // Added in version 3, before which every level was played in the usual arena
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) arena: Option<Arena>,
+    // Added in version 4, before which the walls were always the sides of the arena
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) walls: Option<Vec<(f32, f32)>>,
+    pub(crate) bricks: Vec<LevelBrick>,
+}
+
+// Levels from before there was a schema, written by the first version of the editor.
+// Version 2 only adds the schema, version 3 the arena and version 4 the walls,
+// the rest stayed the same.
+#[derive(Deserialize)]
+struct LevelV1 {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    author: String,
+    bricks: Vec<LevelBrick>,
+}
+
+impl From<LevelV1> for Level {
+    fn from(level: LevelV1) -> Self {
+        Level {
+            schema: LEVEL_SCHEMA,
+            name: level.name,
+            author: level.author,
+            arena: None,
+            walls: None,
+            bricks: level.bricks,
+        }
+    }
+}
+
+// Read before anything else, to know what to read the rest of the file as
+#[derive(Deserialize)]
+pub(crate) struct SchemaVersion {
+    #[serde(default = "first_schema")]
+    pub(crate) schema: u32,
+}
+
+fn first_schema() -> u32 {
+    1
+}
+
+#[derive(Debug)]
+pub(crate) enum LevelError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    // Made with a newer version of the game
+    UnknownSchema(u32),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "{err}"),
+            LevelError::Parse(err) => write!(f, "invalid level: {err}"),
+            LevelError::Serialize(err) => write!(f, "could not write the level: {err}"),
+            LevelError::UnknownSchema(schema) => write!(
+                f,
+                "the level uses schema {schema}, but this version of the game only knows up to {LEVEL_SCHEMA}"
+            ),
+        }
+    }
+}
+
+impl Level {
+    /// The usual grid of normal bricks, in the usual arena
+    pub(crate) fn grid(tunables: &Tunables) -> Self {
+        let layout = BrickLayout::new(tunables, &Arena::default());
+        let bricks = (0..layout.n_rows)
+            .flat_map(|row| (0..layout.n_columns).map(move |column| (row, column)))
+            .map(|(row, column)| {
+                let position = layout.position(row, column);
+                LevelBrick {
+                    position: (position.x, position.y),
+                    kind: BrickKind::Normal,
+                }
+            })
+            .collect();
+
+        Level {
+            schema: LEVEL_SCHEMA,
+            name: "Untitled".to_string(),
+            author: String::new(),
+            arena: None,
+            walls: None,
+            bricks,
+        }
+    }
+
+    /// The arena the level is played in, never smaller than the smallest one there can be
+    pub(crate) fn arena(&self) -> Arena {
+        self.arena.unwrap_or_default().at_least_min_size()
+    }
+
+    /// The walls around the arena the level is played in
+    pub(crate) fn walls(&self) -> WallOutline {
+        match &self.walls {
+            Some(points) => {
+                WallOutline::new(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect())
+            }
+            None => WallOutline::rectangle(&self.arena()),
+        }
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Self, LevelError> {
+        let text = fs::read_to_string(path).map_err(LevelError::Io)?;
+        Level::parse(&text)
+    }
+
+    /// Reads a level in any known version of the format, migrating it to the current one
+    pub(crate) fn parse(text: &str) -> Result<Self, LevelError> {
+        let SchemaVersion { schema } = ron::from_str(text).map_err(LevelError::Parse)?;
+        match schema {
+            1 => ron::from_str::<LevelV1>(text)
+                .map(Level::from)
+                .map_err(LevelError::Parse),
+            // Versions 3 and 4 only add the arena and the walls, which older levels go without
+            2 | 3 | LEVEL_SCHEMA => ron::from_str(text)
+                .map(|level| Level {
+                    schema: LEVEL_SCHEMA,
+                    ..level
+                })
+                .map_err(LevelError::Parse),
+            _ => Err(LevelError::UnknownSchema(schema)),
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), LevelError> {
+        let level = Level {
+            schema: LEVEL_SCHEMA,
+            ..self.clone()
+        };
+        let text = ron::ser::to_string_pretty(&level, ron::ser::PrettyConfig::default())
+            .map_err(LevelError::Serialize)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(LevelError::Io)?;
+        }
+        fs::write(path, text).map_err(LevelError::Io)
+    }
+
+    pub(crate) fn spawn_bricks(
+        &self,
+        commands: &mut Commands,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        brick_assets: &mut BrickAssets,
+        tunables: &Tunables,
+    ) {
+        for brick in &self.bricks {
+            spawn_brick(
+                commands,
+                meshes,
+                materials,
+                brick_assets,
+                tunables,
+                brick.position(),
+                brick.kind,
+            );
+        }
+    }
+
+    /// The brick at the given position, if any
+    pub(crate) fn brick_at(&mut self, position: Vec2) -> Option<&mut LevelBrick> {
+        self.bricks.iter_mut().find(|brick| brick.is_at(position))
+    }
+}
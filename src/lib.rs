@@ -0,0 +1,1305 @@
+//! A simplified implementation of the classic game "Breakout".
+
+use bevy::{
+    ecs::schedule::ShouldRun,
+    prelude::*,
+    sprite::MaterialMesh2dBundle,
+    time::FixedTimestep,
+    transform::TransformSystem,
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+mod arena;
+mod autopilot;
+mod balance;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod broadphase;
+mod collision;
+mod debug_overlay;
+mod editor;
+mod endless;
+mod env;
+mod headless;
+mod interpolation;
+mod leaderboard;
+mod level;
+mod lint;
+mod lockstep;
+mod pack;
+#[cfg(feature = "rapier")]
+mod physics;
+mod pixel_art;
+mod remote;
+mod rng;
+mod rollback;
+mod snapshot;
+mod text_bricks;
+mod time_attack;
+mod trajectory;
+mod tunables;
+mod versus;
+mod wall;
+
+use arena::Arena;
+use collision::Collider;
+use interpolation::Interpolated;
+use level::{BrickKind, Level};
+use tunables::Tunables;
+use wall::*;
+
+#[cfg(feature = "rapier")]
+use physics::check_for_collisions;
+
+pub use balance::{simulate_games, BalanceConfig, BalanceReport};
+pub use env::{BreakoutEnv, StepResult};
+pub use lint::{lint_level_file, lint_pack, LevelProblem, Severity};
+pub use pack::{LEVELS_DIRECTORY, MANIFEST_FILE};
+pub use pixel_art::import_pixel_art;
+
+// Defines the amount of time that should elapse between each physics step.
+const TIME_STEP: f32 = 1.0 / 60.0;
+
+// These constants are defined in `Transform` units.
+// Using the default 2D camera they correspond 1:1 with screen pixels.
+// The sizes, speeds and colors that are worth tweaking live in `Tunables` instead.
+const GAP_BETWEEN_PADDLE_AND_FLOOR: f32 = 60.0;
+// How close can the paddle get to the wall
+const PADDLE_PADDING: f32 = 10.0;
+
+const PLAYER_ONE_KEYS: PlayerControlled = PlayerControlled {
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+};
+const PLAYER_TWO_KEYS: PlayerControlled = PlayerControlled {
+    left: KeyCode::Z,
+    right: KeyCode::X,
+};
+
+const STARTING_LIVES: usize = 3;
+
+// We set the z-value of the ball to 1 so it renders on top in the case of overlapping sprites.
+const BALL_STARTING_POSITION: Vec3 = Vec3::new(0.0, -50.0, 0.0);
+// The ball is drawn and collides as a sphere of this radius
+const BALL_RADIUS: f32 = 15.0;
+const INITIAL_BALL_DIRECTION: Vec2 = Vec2::new(0.5, -0.5);
+
+// The arena's size is up to the level, see `arena`
+const WALL_THICKNESS: f32 = 20.0;
+
+// Where the camera looks at the arena from. `arena::fit_camera` moves it closer or further away,
+// depending on the size of the arena and the window.
+const CAMERA_POSITION: Vec3 = Vec3::new(-300.0, 100.5, 750.0);
+
+const SCOREBOARD_FONT_SIZE: f32 = 40.0;
+const SCOREBOARD_TEXT_PADDING: Val = Val::Px(5.0);
+
+/// Runs the game, as configured on the command line
+pub fn run() {
+    let mode = GameMode::from_args();
+    let autopilot = std::env::args().any(|arg| arg == "--autopilot");
+    let practice = std::env::args().any(|arg| arg == "--practice");
+    let lockstep = lockstep::LockstepConfig::from_args();
+    let remote = arg_value("--remote");
+    let tunables = Tunables::load();
+    let editor = editor::Editor::from_args(&tunables);
+    let packs = std::env::args().any(|arg| arg == "--packs");
+    let level = arg_value("--level").map(|path| {
+        Level::load(std::path::Path::new(&path))
+            .unwrap_or_else(|err| panic!("Could not load the level {path}: {err}"))
+    });
+    // Spelling out a message takes the place of the level's bricks
+    let level = match arg_value("--text") {
+        Some(text) => {
+            let font = arg_value("--font").unwrap_or_else(|| text_bricks::DEFAULT_FONT.to_string());
+            let level = text_bricks::text_level(&text, std::path::Path::new(&font), &tunables, level.as_ref())
+                .unwrap_or_else(|err| panic!("Could not make a level out of {text:?}: {err}"));
+            Some(level)
+        }
+        None => level,
+    };
+    if lockstep.is_some() && remote.is_some() {
+        panic!("Network games can't be remote controlled");
+    }
+    // Both ends of a network game have to make the same random choices
+    let rng = match arg_value("--seed").and_then(|seed| seed.parse().ok()) {
+        Some(seed) => rng::SimRng::new(seed),
+        None if lockstep.is_some() => rng::SimRng::new(0),
+        None => rng::SimRng::from_time(),
+    };
+
+    let fixed_step = FixedTimestep::step(TIME_STEP as f64)
+        .with_label(interpolation::FIXED_STEP_LABEL)
+        .pipe(while_playing);
+    let mut physics = match lockstep {
+        // Over the network, a step can only be taken once both players' inputs for it are in
+        Some(_) => SystemSet::new().with_run_criteria(fixed_step.pipe(lockstep::wait_for_inputs)),
+        // A remote client may take over and step the game at its own pace
+        None if remote.is_some() => {
+            SystemSet::new().with_run_criteria(fixed_step.pipe(remote::unless_stepping))
+        }
+        None => SystemSet::new().with_run_criteria(fixed_step),
+    };
+    // physics = physics.with_system(animate_light_direction);
+    physics = match lockstep {
+        // With rollback, a step first redoes the steps simulated with a wrong guess of the remote input
+        Some(config) if config.rollback => physics.with_system(rollback::run_game_step),
+        _ => physics.with_system(run_game_step),
+    };
+
+    let mut app = App::new();
+    app.insert_resource(AmbientLight {
+            color: Color::WHITE,
+            brightness: 1.0 / 5.0f32,
+        })
+        // Picks up changes to the tunables while the game runs
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: true,
+            ..default()
+        }))
+        .add_plugin(MaterialPlugin::<LineMaterial>::default())
+        .add_asset::<Tunables>()
+        .init_asset_loader::<tunables::TunablesLoader>()
+        .insert_resource(ClearColor(tunables.colors.background))
+        .add_startup_system(tunables::watch_tunables)
+        .add_system(tunables::reload_tunables)
+        .add_system(tunables::apply_tunables.after(tunables::reload_tunables))
+        .add_system(arena::fit_camera)
+        .insert_resource(leaderboard::Leaderboard::load())
+        .add_system_to_stage(CoreStage::PreUpdate, interpolation::restore_simulated_positions)
+        .add_system_set(physics)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            interpolation::interpolate_positions.before(TransformSystem::TransformPropagate),
+        )
+        .add_system(camera_movement)
+        // Shows what the ball is tested against, with F3
+        .init_resource::<debug_overlay::DebugOverlay>()
+        .add_startup_system(debug_overlay::setup_overlay)
+        .add_system(debug_overlay::update_overlay)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            debug_overlay::draw_overlay.after(interpolation::interpolate_positions),
+        )
+        // Shows where the ball is going
+        .insert_resource(trajectory::TrajectoryPreview::new(practice))
+        .add_startup_system(trajectory::setup_trajectory_line)
+        .add_system(trajectory::track_serves)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            trajectory::draw_trajectory.before(interpolation::interpolate_positions),
+        )
+        .add_system(update_scoreboard)
+        .add_system(leaderboard::record_high_score)
+        .add_system(show_game_over.after(leaderboard::record_high_score))
+        .add_system(bevy::window::close_on_esc);
+
+    if autopilot {
+        // Runs once `setup` has spawned the paddles
+        app.add_startup_system_to_stage(StartupStage::PostStartup, autopilot::take_over_paddle);
+    }
+
+    if let Some(config) = lockstep {
+        match lockstep::Lockstep::connect(config) {
+            Ok(lockstep) => {
+                app.insert_resource(lockstep).add_startup_system_to_stage(
+                    StartupStage::PostStartup,
+                    lockstep::setup_lockstep.before(autopilot::take_over_paddle),
+                );
+            }
+            Err(err) => panic!("Could not open the network socket: {err}"),
+        }
+        if config.rollback {
+            app.init_resource::<rollback::Rollback>();
+        }
+    } else {
+        // Loading a save would throw the other player off
+        app.init_resource::<snapshot::SaveState>()
+            .add_system(snapshot::quick_save_and_load);
+    }
+
+    if let Some(address) = remote {
+        match remote::RemoteControl::listen(&address) {
+            Ok(remote) => {
+                app.insert_resource(remote)
+                    // Runs once `setup` has spawned the paddles
+                    .add_startup_system_to_stage(StartupStage::PostStartup, remote::take_over_paddles)
+                    .add_system(remote::serve_clients);
+            }
+            Err(err) => panic!("Could not listen for remote control on {address}: {err}"),
+        }
+    }
+
+    if let Some((editor, level)) = editor {
+        app.insert_resource(editor)
+            .insert_resource(level)
+            .insert_resource(RoundState::Editing)
+            .add_startup_system(editor::setup_editor)
+            .add_system(editor::place_bricks)
+            .add_system(editor::show_level.after(editor::place_bricks))
+            .add_system(editor::toggle_test_play.before(editor::edit_metadata))
+            .add_system(editor::edit_metadata)
+            .add_system(editor::save_level)
+            .add_system(editor::update_editor_text);
+    } else if packs {
+        // No bricks until a pack has been picked
+        let empty = Level {
+            bricks: Vec::new(),
+            ..Level::grid(&tunables)
+        };
+        app.insert_resource(pack::PackSelect::discover())
+            .insert_resource(empty)
+            .insert_resource(RoundState::SelectingPack)
+            .add_startup_system(pack::setup_pack_select)
+            .add_system(pack::choose_pack.before(pack::continue_pack))
+            .add_system(pack::continue_pack.after(show_game_over))
+            .add_system(pack::update_pack_select_text);
+    } else if let Some(level) = level {
+        app.insert_resource(level);
+    }
+
+    app.insert_resource(tunables);
+    add_game_rules(&mut app, mode, rng, lockstep.is_some());
+    app.run();
+}
+
+// Everything needed to play the game, with or without a window:
+// the resources, the entities and the systems making up a step
+fn add_game_rules(app: &mut App, mode: GameMode, rng: rng::SimRng, networked: bool) {
+    let (arena, walls) = match app.world.get_resource::<Level>() {
+        Some(level) => (level.arena(), level.walls()),
+        None => (Arena::default(), WallOutline::rectangle(&Arena::default())),
+    };
+    app.insert_resource(Scoreboard {
+            score: 0,
+            lives: STARTING_LIVES,
+        })
+        .insert_resource(mode)
+        .init_resource::<Tunables>()
+        .insert_resource(arena)
+        .insert_resource(walls)
+        .init_resource::<broadphase::Broadphase>()
+        .init_resource::<RoundState>()
+        .init_resource::<interpolation::Interpolation>()
+        .init_resource::<BrickAssets>()
+        .init_resource::<NextBrickId>()
+        .insert_resource(rng)
+        .add_startup_system(setup)
+        .add_startup_system(wall::wall_setup)
+        .add_system(arena::fit_arena)
+        .add_event::<CollisionEvent>()
+        .add_event::<PaddleHitEvent>()
+        .add_event::<BrickDestroyedEvent>()
+        .add_event::<BrickHitEvent>()
+        .add_event::<BallLostEvent>()
+        .insert_resource(GameStep(game_step(mode, networked)));
+    #[cfg(feature = "rapier")]
+    physics::add_physics(app);
+
+    match mode {
+        GameMode::Classic | GameMode::CoOp(_) => {}
+        GameMode::Endless => {
+            app.init_resource::<endless::EndlessState>();
+        }
+        GameMode::TimeAttack => {
+            app.init_resource::<time_attack::TimeAttackRules>()
+                // Runs once `setup` has spawned the bricks
+                .add_startup_system_to_stage(StartupStage::PostStartup, time_attack::setup_time_attack)
+                .add_system(time_attack::update_countdown_text);
+        }
+        GameMode::Versus => {
+            app.init_resource::<versus::VersusScores>()
+                // Runs once `setup` has spawned the bricks and the scoreboard
+                .add_startup_system_to_stage(StartupStage::PostStartup, versus::setup_versus)
+                .add_system(versus::update_versus_scoreboard)
+                .add_system(versus::announce_winner);
+        }
+    }
+}
+
+/// Which set of rules the game is played under, picked with `--mode <name>`
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum GameMode {
+    #[default]
+    Classic,
+    Endless,
+    TimeAttack,
+    // Two players on one keyboard, sharing lives and score
+    CoOp(CoOpLayout),
+    // Two players on one keyboard, each defending their own wall
+    Versus,
+}
+
+/// Where the two paddles of a co-op game go, picked with `--layout <name>`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum CoOpLayout {
+    // Both at the bottom, each with their own half of the arena
+    #[default]
+    SideBySide,
+    // One defending the bottom wall and one the top wall
+    TopAndBottom,
+}
+
+// The value following `name` on the command line, if any
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != name);
+    args.next()?;
+    args.next()
+}
+
+impl GameMode {
+    fn from_args() -> Self {
+        match arg_value("--mode").as_deref() {
+            Some("endless") => GameMode::Endless,
+            Some("time-attack") => GameMode::TimeAttack,
+            Some("co-op") => {
+                let layout = match arg_value("--layout").as_deref() {
+                    Some("top-bottom") => CoOpLayout::TopAndBottom,
+                    _ => CoOpLayout::SideBySide,
+                };
+                GameMode::CoOp(layout)
+            }
+            Some("versus") => GameMode::Versus,
+            Some("classic") | None => GameMode::Classic,
+            Some(other) => {
+                warn!("Unknown game mode {other:?}, falling back to classic");
+                GameMode::Classic
+            }
+        }
+    }
+
+    /// Name used on the command line and as the leaderboard category
+    fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::Endless => "endless",
+            GameMode::TimeAttack => "time-attack",
+            GameMode::CoOp(_) => "co-op",
+            GameMode::Versus => "versus",
+        }
+    }
+
+    /// Whether there is a paddle defending the top wall as well as the bottom one
+    fn two_sided(self) -> bool {
+        matches!(
+            self,
+            GameMode::CoOp(CoOpLayout::TopAndBottom) | GameMode::Versus
+        )
+    }
+
+    // Where the ball is served from, away from the bricks
+    fn ball_starting_position(self, tunables: &Tunables, arena: &Arena) -> Vec3 {
+        if self.two_sided() {
+            let gap = tunables.gap_between_paddle_and_bricks_two_sided;
+            Vec3::new(0.0, arena.paddle_y() + gap / 2.0, 0.0)
+        } else {
+            BALL_STARTING_POSITION
+        }
+    }
+}
+
+/// Whether the current round is still being played.
+/// The physics step is paused once the round is over, won or lost.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RoundState {
+    #[default]
+    Playing,
+    GameOver,
+    Cleared,
+    // Building a level in the editor, see `editor`
+    Editing,
+    // Picking a level pack to play, see `pack`
+    SelectingPack,
+}
+
+// Only let the fixed timestep through while the round is in progress
+fn while_playing(In(should_run): In<ShouldRun>, round: Res<RoundState>) -> ShouldRun {
+    match *round {
+        RoundState::Playing => should_run,
+        RoundState::GameOver
+        | RoundState::Cleared
+        | RoundState::Editing
+        | RoundState::SelectingPack => ShouldRun::No,
+    }
+}
+
+/// Everything that happens during one fixed step of the game.
+/// It is kept in its own stage so a step can also be run on demand,
+/// e.g. to simulate the same steps again after rolling the game back.
+#[derive(Resource)]
+struct GameStep(SystemStage);
+
+// Systems in a single threaded stage always run in the same order,
+// which keeps networked games deterministic
+fn game_step(mode: GameMode, networked: bool) -> SystemStage {
+    let mut step = SystemStage::single_threaded()
+        .with_system(check_for_collisions)
+        .with_system(keyboard_paddle_input.before(move_paddle))
+        .with_system(autopilot::drive_paddle.before(move_paddle).before(apply_velocity))
+        .with_system(move_paddle.before(check_for_collisions))
+        .with_system(apply_velocity.before(check_for_collisions));
+        // .with_system(play_collision_sound.after(check_for_collisions)),
+
+    // Rapier finds what is near the ball on its own
+    #[cfg(not(feature = "rapier"))]
+    step.add_system(
+        broadphase::update_broadphase
+            .after(move_paddle)
+            .before(check_for_collisions),
+    );
+
+    if networked {
+        step.add_system(
+            lockstep::apply_inputs
+                .after(keyboard_paddle_input)
+                .after(autopilot::drive_paddle)
+                .before(move_paddle)
+                .before(apply_velocity),
+        );
+    }
+
+    match mode {
+        GameMode::Classic => {
+            step.add_system(check_level_cleared.after(check_for_collisions));
+        }
+        GameMode::CoOp(_) => {
+            step.add_system(lose_ball.after(check_for_collisions))
+                .add_system(check_level_cleared.after(lose_ball));
+        }
+        GameMode::Endless => {
+            step.add_system(endless::descend_bricks.after(check_for_collisions));
+        }
+        GameMode::TimeAttack => {
+            step.add_system(check_level_cleared.after(check_for_collisions))
+                .add_system(time_attack::count_down.after(check_level_cleared));
+        }
+        GameMode::Versus => {
+            step.add_system(versus::score_points.after(check_for_collisions));
+        }
+    }
+
+    step
+}
+
+// Takes a single step of the game
+fn run_game_step(world: &mut World) {
+    #[cfg(feature = "rapier")]
+    physics::run_physics_step(world);
+    world.resource_scope(|world, mut step: Mut<GameStep>| step.0.run(world));
+    interpolation::record_step(world);
+}
+
+#[derive(Component)]
+struct Paddle;
+
+// Paddles, kept apart from the ball so systems can move both
+type PaddleFilter = (With<Paddle>, Without<Ball>);
+
+// Horizontal direction a paddle should move in this step, from -1.0 (left) to 1.0 (right).
+// Written by whatever controls the paddle, and applied by `move_paddle`.
+#[derive(Component, Default)]
+struct PaddleInput(f32);
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    fn index(self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+
+    fn opponent(self) -> Self {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Player::One => "Player One",
+            Player::Two => "Player Two",
+        }
+    }
+
+    // Keys used when both players share a keyboard
+    fn keys(self) -> PlayerControlled {
+        match self {
+            Player::One => PLAYER_ONE_KEYS,
+            Player::Two => PLAYER_TWO_KEYS,
+        }
+    }
+
+    fn paddle_color(self, tunables: &Tunables) -> Color {
+        match self {
+            Player::One => tunables.colors.player_one_paddle,
+            Player::Two => tunables.colors.player_two_paddle,
+        }
+    }
+}
+
+// Paddles with this component are steered with the keyboard, using the given keys
+#[derive(Component)]
+struct PlayerControlled {
+    left: KeyCode,
+    right: KeyCode,
+}
+
+/// The horizontal stretch of the arena a paddle may move in
+#[derive(Component, Clone, Copy)]
+struct PaddleLane {
+    left: f32,
+    right: f32,
+}
+
+impl PaddleLane {
+    fn full_width(arena: &Arena) -> Self {
+        PaddleLane {
+            left: arena.left() + WALL_THICKNESS / 2.0,
+            right: arena.right() - WALL_THICKNESS / 2.0,
+        }
+    }
+
+    fn center(&self) -> f32 {
+        (self.left + self.right) / 2.0
+    }
+}
+
+// Which paddles the mode has, with the lane and height of each
+fn paddle_spots(mode: GameMode, arena: &Arena) -> Vec<(Player, PaddleLane, f32)> {
+    let full_width = PaddleLane::full_width(arena);
+    match mode {
+        GameMode::CoOp(CoOpLayout::SideBySide) => {
+            let center = full_width.center();
+            vec![
+                (Player::One, PaddleLane { left: full_width.left, right: center }, arena.paddle_y()),
+                (Player::Two, PaddleLane { left: center, right: full_width.right }, arena.paddle_y()),
+            ]
+        }
+        GameMode::CoOp(CoOpLayout::TopAndBottom) | GameMode::Versus => vec![
+            (Player::One, full_width, arena.paddle_y()),
+            (Player::Two, full_width, arena.top_paddle_y()),
+        ],
+        _ => vec![(Player::One, full_width, arena.paddle_y())],
+    }
+}
+
+#[derive(Component)]
+struct Ball;
+
+#[derive(Component, Deref, DerefMut)]
+struct Velocity(Vec2);
+
+// Sent whenever the ball hits something
+struct CollisionEvent {
+    // Where the ball touched it
+    point: Vec2,
+}
+
+// Sent in addition to `CollisionEvent` when the ball bounces off a paddle
+#[derive(Default)]
+struct PaddleHitEvent;
+
+#[derive(Component)]
+struct Brick;
+
+// Numbers bricks in the order they are spawned in. Unlike their `Entity`, it stays the same
+// when a rollback spawns a brick again, so every peer handles bricks in the same order.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+struct BrickId(u32);
+
+// The id the next brick spawned gets
+#[derive(Resource, Clone, Copy, Default)]
+struct NextBrickId(u32);
+
+// The ball is lost when it touches a wall with this component
+#[derive(Component)]
+struct LossZone;
+
+// Sent when the ball gets past a paddle and touches a loss zone
+struct BallLostEvent {
+    ball: Entity,
+    zone: Entity,
+}
+
+// Sent when the ball breaks a brick, before the brick entity is despawned
+struct BrickDestroyedEvent(Entity);
+
+// Sent whenever the ball hits a brick, whether or not it breaks
+struct BrickHitEvent(Entity);
+
+#[derive(Resource)]
+struct CollisionSound(Handle<AudioSource>);
+
+// This resource tracks the game's score
+#[derive(Resource, Clone, Debug)]
+struct Scoreboard {
+    score: usize,
+    // Shared by every paddle; the game is over once they run out
+    lives: usize,
+}
+
+// Marks the text announcing the end of the round
+#[derive(Component)]
+struct GameOverText;
+
+// Marks the text entity that displays the score
+#[derive(Component)]
+struct ScoreboardText;
+
+/// The grid bricks are laid out on.
+/// The number of rows and columns is computed from the space left in the arena.
+struct BrickLayout {
+    n_columns: usize,
+    n_rows: usize,
+    // Center of the bottom-left brick
+    origin: Vec2,
+    // Distance between the centers of neighbouring bricks
+    step: Vec2,
+}
+
+impl BrickLayout {
+    fn new(tunables: &Tunables, arena: &Arena) -> Self {
+        BrickLayout::in_band(
+            tunables,
+            arena,
+            arena.paddle_y() + tunables.gap_between_paddle_and_bricks,
+            arena.top() - tunables.gap_between_bricks_and_ceiling,
+        )
+    }
+
+    // With paddles on both ends, the bricks sit in the middle,
+    // equally far away from either paddle
+    fn two_sided(tunables: &Tunables, arena: &Arena) -> Self {
+        let gap = tunables.gap_between_paddle_and_bricks_two_sided;
+        let (paddle_y, top_paddle_y) = (arena.paddle_y(), arena.top_paddle_y());
+        let mut layout = BrickLayout::in_band(tunables, arena, paddle_y + gap, top_paddle_y - gap);
+
+        let height_of_bricks = layout.n_rows as f32 * layout.step.y - tunables.gap_between_bricks;
+        let center_y = (paddle_y + top_paddle_y) / 2.0;
+        layout.origin.y = center_y - height_of_bricks / 2.0 + tunables.brick_size.y / 2.0;
+        layout
+    }
+
+    fn for_mode(mode: GameMode, tunables: &Tunables, arena: &Arena) -> Self {
+        if mode.two_sided() {
+            BrickLayout::two_sided(tunables, arena)
+        } else {
+            BrickLayout::new(tunables, arena)
+        }
+    }
+
+    // The bottom edge of the bricks is exact, the top one only an upper bound
+    fn in_band(
+        tunables: &Tunables,
+        arena: &Arena,
+        bottom_edge_of_bricks: f32,
+        top_edge_of_bricks: f32,
+    ) -> Self {
+        let brick_size = tunables.brick_size;
+        let step = tunables.brick_step();
+
+        // Negative scales result in flipped sprites / meshes,
+        // which is definitely not what we want here
+        assert!(brick_size.x > 0.0);
+        assert!(brick_size.y > 0.0);
+
+        let total_width_of_bricks =
+            arena.width - 2. * tunables.gap_between_bricks_and_sides;
+        let total_height_of_bricks = top_edge_of_bricks - bottom_edge_of_bricks;
+
+        assert!(total_width_of_bricks > 0.0);
+        assert!(total_height_of_bricks > 0.0);
+
+        // Given the space available, compute how many rows and columns of bricks we can fit
+        let n_columns = (total_width_of_bricks / step.x).floor() as usize;
+        let n_rows = (total_height_of_bricks / step.y).floor() as usize;
+        let n_vertical_gaps = n_columns - 1;
+
+        // Because we need to round the number of columns,
+        // the space on the top and sides of the bricks only captures a lower bound, not an exact value
+        let center_of_bricks = (arena.left() + arena.right()) / 2.0;
+        let left_edge_of_bricks = center_of_bricks
+            // Space taken up by the bricks
+            - (n_columns as f32 / 2.0 * brick_size.x)
+            // Space taken up by the gaps
+            - n_vertical_gaps as f32 / 2.0 * tunables.gap_between_bricks;
+
+        // In Bevy, the `translation` of an entity describes the center point,
+        // not its bottom-left corner
+        let origin = Vec2::new(
+            left_edge_of_bricks + brick_size.x / 2.,
+            bottom_edge_of_bricks + brick_size.y / 2.,
+        );
+
+        BrickLayout {
+            n_columns,
+            n_rows,
+            origin,
+            step,
+        }
+    }
+
+    /// Center of the brick in the given cell
+    fn position(&self, row: usize, column: usize) -> Vec2 {
+        self.origin + Vec2::new(column as f32, row as f32) * self.step
+    }
+}
+
+fn spawn_paddle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    tunables: &Tunables,
+    position: Vec2,
+    lane: PaddleLane,
+    player: Player,
+) {
+    let size = tunables.paddle_size;
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(shape::Box::new(size.x, size.y, size.z).into()).into(),
+            material: materials.add(player.paddle_color(tunables).into()),
+            transform: Transform {
+                translation: position.extend(0.0),
+                ..default()
+            },
+            ..default()
+        },
+        Paddle,
+        player,
+        PaddleInput::default(),
+        lane,
+        player.keys(),
+        Collider::Aabb(Vec2::new(size.x, size.y)),
+        Interpolated::new(position.extend(0.0)),
+    ));
+}
+
+/// The meshes and materials of bricks, made once for every size and kind and shared by
+/// every brick after that, so that they can be drawn together
+#[derive(Resource, Default)]
+struct BrickAssets {
+    meshes: HashMap<[u32; 2], Handle<Mesh>>,
+    materials: HashMap<BrickKind, Handle<StandardMaterial>>,
+}
+
+impl BrickAssets {
+    fn mesh(&mut self, meshes: &mut Assets<Mesh>, size: Vec2) -> Handle<Mesh> {
+        self.meshes
+            .entry([size.x.to_bits(), size.y.to_bits()])
+            .or_insert_with(|| meshes.add(shape::Box::new(size.x, size.y, 4.0).into()))
+            .clone()
+    }
+
+    fn material(
+        &mut self,
+        materials: &mut Assets<StandardMaterial>,
+        tunables: &Tunables,
+        kind: BrickKind,
+    ) -> Handle<StandardMaterial> {
+        self.materials
+            .entry(kind)
+            .or_insert_with(|| materials.add(kind.color(tunables).into()))
+            .clone()
+    }
+
+    // Every brick of a kind shares its material, so this recolors all of them at once
+    fn recolor(&self, materials: &mut Assets<StandardMaterial>, tunables: &Tunables) {
+        for (kind, material) in &self.materials {
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color = kind.color(tunables);
+            }
+        }
+    }
+}
+
+fn spawn_brick(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    brick_assets: &mut BrickAssets,
+    tunables: &Tunables,
+    position: Vec2,
+    kind: BrickKind,
+) {
+    let size = tunables.brick_size;
+    let brick = commands.spawn((
+        PbrBundle {
+            mesh: brick_assets.mesh(meshes, size),
+            material: brick_assets.material(materials, tunables, kind),
+            transform: Transform {
+                translation: position.extend(0.0),
+                ..default()
+            },
+            ..default()
+        },
+        Brick,
+        kind,
+        Collider::OrientedBox(size),
+    )).id();
+    commands.add(move |world: &mut World| {
+        let mut next_id = world.resource_mut::<NextBrickId>();
+        let id = BrickId(next_id.0);
+        next_id.0 += 1;
+        if let Some(mut brick) = world.get_entity_mut(brick) {
+            brick.insert(id);
+        }
+    });
+}
+
+// Add the game's entities to our world
+#[allow(clippy::too_many_arguments)]
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    asset_server: Res<AssetServer>,
+    mode: Res<GameMode>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    level: Option<Res<Level>>,
+) {
+    use std::f32::consts::PI;
+
+    // light
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            intensity: 1500.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(-300.0, 200.5, 750.0),
+        ..default()
+    });
+
+    const HALF_SIZE: f32 = 10.0;
+    let mut transform = Transform {
+        translation: (CAMERA_POSITION + Vec3::new(0.0, 200.0, 0.0)),
+        rotation: Quat::from_rotation_x(-PI / 4.),
+        ..default()
+    };
+    transform.look_at(Vec3::ZERO, Vec3::Y);
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            // Configure the projection to better fit the scene
+            shadow_projection: OrthographicProjection {
+                left: -HALF_SIZE,
+                right: HALF_SIZE,
+                bottom: -HALF_SIZE,
+                top: HALF_SIZE,
+                near: -10.0 * HALF_SIZE,
+                far: 10.0 * HALF_SIZE,
+                ..default()
+            },
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: transform,
+        ..default()
+    });
+
+    // Camera
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_translation(CAMERA_POSITION).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+    // Sound
+    let ball_collision_sound = asset_server.load("sounds/breakout_collision.ogg");
+    commands.insert_resource(CollisionSound(ball_collision_sound));
+
+    // Paddles
+    for (player, lane, y) in paddle_spots(*mode, &arena) {
+        let position = Vec2::new(lane.center(), y);
+        spawn_paddle(&mut commands, &mut meshes, &mut materials, &tunables, position, lane, player);
+    }
+
+    // // plane
+    // commands.spawn(PbrBundle {
+    //     mesh: meshes.add(Mesh::from(shape::Plane { size: 50.0 })),
+    //     material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+    //     ..default()
+    // });
+    
+    // Ball
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(shape::Icosphere {
+                radius: BALL_RADIUS,
+                subdivisions: 32,
+            }.into()).into(),
+            // material: materials.add(BALL_COLOR.into()),
+            material: materials.add(StandardMaterial {
+                // base_color: Color::hex("ffd891").unwrap(),
+                base_color: tunables.colors.ball,
+                // vary key PBR parameters on a grid of spheres to show the effect
+                metallic: 0.5,
+                perceptual_roughness: 0.5,
+                ..default()
+            }),
+            transform: Transform::from_translation(mode.ball_starting_position(&tunables, &arena)),
+            ..default()
+        },
+        Ball,
+        Velocity(tunables.serve_velocity()),
+        Collider::Circle(BALL_RADIUS),
+        Interpolated::new(mode.ball_starting_position(&tunables, &arena)),
+    ));
+
+    // Scoreboard
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Score: ",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    color: tunables.colors.text,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+            }),
+            TextSection::new(
+                "  Lives: ",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    color: tunables.colors.text,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: SCOREBOARD_TEXT_PADDING,
+                left: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+            ..default()
+        }),
+        ScoreboardText,
+    ));
+
+    // Walls
+    // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Right));
+    // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Left));
+    // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Bottom));
+    // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Top));
+
+    // Bricks
+    if let Some(level) = level {
+        level.spawn_bricks(&mut commands, &mut meshes, &mut materials, &mut brick_assets, &tunables);
+        return;
+    }
+
+    let layout = BrickLayout::for_mode(*mode, &tunables, &arena);
+
+    for row in 0..layout.n_rows {
+        for column in 0..layout.n_columns {
+            let brick_position = layout.position(row, column);
+            spawn_brick(&mut commands, &mut meshes, &mut materials, &mut brick_assets, &tunables, brick_position, BrickKind::Normal);
+        }
+    }
+}
+
+fn keyboard_paddle_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut query: Query<(&mut PaddleInput, &PlayerControlled)>,
+) {
+    for (mut input, keys) in &mut query {
+        let mut direction = 0.0;
+
+        if keyboard_input.pressed(keys.left) {
+            direction -= 1.0;
+        }
+
+        if keyboard_input.pressed(keys.right) {
+            direction += 1.0;
+        }
+
+        input.0 = direction;
+    }
+}
+
+fn move_paddle(
+    tunables: Res<Tunables>,
+    mut query: Query<(&mut Transform, &PaddleInput, &PaddleLane), With<Paddle>>,
+) {
+    let half_width = tunables.paddle_size.x / 2.0;
+    for (mut paddle_transform, input, lane) in &mut query {
+        let direction = input.0.clamp(-1.0, 1.0);
+
+        // Calculate the new horizontal paddle position based on player input
+        let new_paddle_position =
+            paddle_transform.translation.x + direction * tunables.paddle_speed * TIME_STEP;
+
+        // Update the paddle position,
+        // making sure it doesn't leave its lane, and with it the arena
+        let left_bound = lane.left + half_width + PADDLE_PADDING;
+        let right_bound = lane.right - half_width - PADDLE_PADDING;
+
+        paddle_transform.translation.x = new_paddle_position.clamp(left_bound, right_bound);
+    }
+}
+
+// For controllers living outside of the game step.
+// `move_paddle` picks the input up from there, as it would the keyboard's.
+fn set_paddle_input(world: &mut World, player: Player, input: f32) {
+    let mut query = world.query_filtered::<(&Player, &mut PaddleInput), With<Paddle>>();
+    for (paddle_player, mut paddle_input) in query.iter_mut(world) {
+        if *paddle_player == player {
+            paddle_input.0 = input.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+// With the `rapier` feature, rapier moves whatever it has a body for instead
+#[cfg(not(feature = "rapier"))]
+type Unsimulated = ();
+#[cfg(feature = "rapier")]
+type Unsimulated = Without<bevy_rapier2d::prelude::RigidBody>;
+
+fn apply_velocity(mut query: Query<(&mut Transform, &Velocity), Unsimulated>) {
+    for (mut transform, velocity) in &mut query {
+        transform.translation.x += velocity.x * TIME_STEP;
+        transform.translation.y += velocity.y * TIME_STEP;
+    }
+}
+
+fn update_scoreboard(
+    scoreboard: Res<Scoreboard>,
+    mut query: Query<&mut Text, With<ScoreboardText>>,
+) {
+    let mut text = query.single_mut();
+    text.sections[1].value = scoreboard.score.to_string();
+    text.sections[3].value = scoreboard.lives.to_string();
+}
+
+// Take a life for every lost ball and serve it again, until there are none left
+fn lose_ball(
+    mode: Res<GameMode>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut round: ResMut<RoundState>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut ball_lost_events: EventReader<BallLostEvent>,
+    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+) {
+    for event in ball_lost_events.iter() {
+        scoreboard.lives = scoreboard.lives.saturating_sub(1);
+        if scoreboard.lives == 0 {
+            *round = RoundState::GameOver;
+            return;
+        }
+
+        if let Ok((mut transform, mut velocity)) = ball_query.get_mut(event.ball) {
+            transform.translation = mode.ball_starting_position(&tunables, &arena);
+            velocity.0 = tunables.serve_velocity();
+        }
+    }
+}
+
+// The round is won once every brick that can be broken is gone
+fn check_level_cleared(mut round: ResMut<RoundState>, brick_query: Query<&BrickKind, With<Brick>>) {
+    if !brick_query.iter().any(|kind| kind.breakable()) {
+        *round = RoundState::Cleared;
+    }
+}
+
+fn show_game_over(
+    mut commands: Commands,
+    round: Res<RoundState>,
+    mode: Res<GameMode>,
+    leaderboard: Res<leaderboard::Leaderboard>,
+    tunables: Res<Tunables>,
+    asset_server: Res<AssetServer>,
+) {
+    // A versus match has a winner instead, see `versus::announce_winner`
+    if !round.is_changed() || *mode == GameMode::Versus {
+        return;
+    }
+    let message = match *round {
+        RoundState::Playing | RoundState::Editing | RoundState::SelectingPack => return,
+        RoundState::GameOver => "Game Over",
+        RoundState::Cleared => "Level Cleared",
+    };
+    let best = leaderboard.best(mode.name()).unwrap_or_default();
+
+    commands.spawn((
+        TextBundle::from_section(
+            format!("{message}\nBest: {best}"),
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 2.0 * SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(40.0),
+                left: Val::Percent(38.0),
+                ..default()
+            },
+            ..default()
+        }),
+        GameOverText,
+    ));
+}
+
+// Everything the ball can run into, and what it is
+type Obstacle = (
+    Entity,
+    &'static Transform,
+    &'static Collider,
+    Option<&'static BrickKind>,
+    Option<&'static Paddle>,
+    Option<&'static LossZone>,
+);
+
+#[cfg(not(feature = "rapier"))]
+#[allow(clippy::too_many_arguments)]
+fn check_for_collisions(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    tunables: Res<Tunables>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut broadphase: ResMut<broadphase::Broadphase>,
+    mut ball_query: Query<(Entity, &mut Velocity, &mut Transform, &Collider), With<Ball>>,
+    collider_query: Query<Obstacle, Without<Ball>>,
+    brick_id_query: Query<&BrickId>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut paddle_hit_events: EventWriter<PaddleHitEvent>,
+    mut brick_destroyed_events: EventWriter<BrickDestroyedEvent>,
+    mut brick_hit_events: EventWriter<BrickHitEvent>,
+    mut ball_lost_events: EventWriter<BallLostEvent>,
+) {
+    let (ball, mut ball_velocity, mut ball_transform, ball_collider) = ball_query.single_mut();
+    let Collider::Circle(ball_radius) = *ball_collider else {
+        panic!("The ball should be a circle");
+    };
+
+    // check collision with the walls, bricks and paddles near the ball
+    let mut nearby = broadphase.near(ball_transform.translation.truncate(), ball_radius);
+    // Bricks come back as other entities after a rollback, so they go by their id instead
+    nearby.sort_by_key(|&entity| (brick_id_query.get(entity).ok().copied(), entity));
+    for (collider_entity, transform, collider, maybe_brick, maybe_paddle, maybe_loss_zone) in
+        collider_query.iter_many(&nearby)
+    {
+        let mut ball_center = ball_transform.translation.truncate();
+        let Some(contact) = collider.contact(transform, ball_center, ball_radius) else {
+            continue;
+        };
+
+        // Sends a collision event so that other systems can react to the collision
+        collision_events.send(CollisionEvent {
+            point: ball_center - contact.normal * (ball_radius - contact.depth),
+        });
+
+        // Bricks should be despawned and increment the scoreboard on collision.
+        // Tough bricks only crack the first time around, and unbreakable ones never break.
+        if maybe_brick.is_some() {
+            brick_hit_events.send(BrickHitEvent(collider_entity));
+        }
+        match maybe_brick {
+            Some(BrickKind::Normal) => {
+                scoreboard.score += 1;
+                brick_destroyed_events.send(BrickDestroyedEvent(collider_entity));
+                commands.entity(collider_entity).despawn();
+                broadphase.remove(collider_entity);
+            }
+            Some(BrickKind::Tough) => {
+                let cracked = BrickKind::Normal;
+                let material = brick_assets.material(&mut materials, &tunables, cracked);
+                commands.entity(collider_entity).insert((cracked, material));
+            }
+            Some(BrickKind::Unbreakable) | None => {}
+        }
+
+        if maybe_paddle.is_some() {
+            paddle_hit_events.send_default();
+        }
+
+        if maybe_loss_zone.is_some() {
+            ball_lost_events.send(BallLostEvent {
+                ball,
+                zone: collider_entity,
+            });
+        }
+
+        contact.bounce(&mut ball_center, &mut ball_velocity.0);
+        ball_transform.translation = ball_center.extend(ball_transform.translation.z);
+    }
+}
+
+fn play_collision_sound(
+    collision_events: EventReader<CollisionEvent>,
+    audio: Res<Audio>,
+    sound: Res<CollisionSound>,
+) {
+    // Play a sound once per frame if a collision occurred.
+    if !collision_events.is_empty() {
+        // This prevents events staying active on the next frame.
+        collision_events.clear();
+        audio.play(sound.0.clone());
+    }
+}
+
+fn animate_light_direction(
+    time: Res<Time>,
+    mut query: Query<&mut Transform, With<DirectionalLight>>,
+) {
+    for mut transform in &mut query {
+        transform.rotate_y(time.delta_seconds() * 0.5);
+
+        transform.look_at(Vec3::ZERO, Vec3::Y);
+    }
+}
+
+fn camera_movement(
+    input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut query: Query<&mut Transform, With<Camera>>,
+) {
+    let mut transform = query.single_mut();
+    
+    let mut direction = Vec3::ZERO;
+    if input.pressed(KeyCode::W) {
+        direction.y += 1.0;
+    }
+    if input.pressed(KeyCode::S) {
+        direction.y -= 1.0;
+    }
+    if input.pressed(KeyCode::A) {
+        direction.x -= 1.0;
+    }
+    if input.pressed(KeyCode::D) {
+        direction.x += 1.0;
+    }
+
+    direction *= 70.0;
+
+    transform.translation += time.delta_seconds() * 2.0 * direction;
+
+    transform.look_at(Vec3::ZERO, Vec3::Y);
+    
+}
\ No newline at end of file
@@ -0,0 +1,523 @@
+//! Checks level files and packs for mistakes, for the `validate-levels` binary.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    arena::{Arena, MIN_ARENA_SIZE},
+    level::{BrickKind, SchemaVersion, LEVEL_SCHEMA},
+    pack::{GameVersion, PackManifest, MANIFEST_FILE},
+    tunables::Tunables,
+    wall::WallOutline,
+    BALL_RADIUS, WALL_THICKNESS,
+};
+
+// The ball needs some room to get in between the paddle and the bricks
+const MIN_GAP_BETWEEN_PADDLE_AND_BRICKS: f32 = 4.0 * BALL_RADIUS;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// The level is broken or can't be finished
+    Error,
+    /// Something looks off, but the level still plays
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct LevelProblem {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for LevelProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}", self.message)
+    }
+}
+
+// Mirrors `level::Level`, but takes any brick kind so unknown ones can be reported
+// along with every other problem, instead of failing to load the whole level
+#[derive(Deserialize)]
+struct LintedLevel {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    arena: Option<Arena>,
+    #[serde(default)]
+    walls: Option<Vec<(f32, f32)>>,
+    bricks: Vec<LintedBrick>,
+}
+
+#[derive(Deserialize)]
+struct LintedBrick {
+    position: (f32, f32),
+    #[serde(default)]
+    kind: KindName,
+}
+
+struct KindName(String);
+
+impl Default for KindName {
+    fn default() -> Self {
+        KindName(format!("{:?}", BrickKind::default()))
+    }
+}
+
+struct KindNameVisitor;
+
+impl<'de> de::Visitor<'de> for KindNameVisitor {
+    type Value = KindName;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a brick kind")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<KindName, E> {
+        Ok(KindName(name.to_string()))
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, kind: A) -> Result<KindName, A::Error> {
+        let (VariantName(name), variant) = kind.variant()?;
+        de::VariantAccess::unit_variant(variant)?;
+        Ok(name)
+    }
+}
+
+// The name of an enum variant, whichever it is
+struct VariantName(KindName);
+
+impl<'de> Deserialize<'de> for VariantName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_identifier(KindNameVisitor)
+            .map(VariantName)
+    }
+}
+
+impl<'de> Deserialize<'de> for KindName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Read as an enum, as ron only takes bare identifiers inside structs for those
+        deserializer.deserialize_enum("BrickKind", &[], KindNameVisitor)
+    }
+}
+
+/// Loads a level file and lists everything wrong with it, with the brick and paddle sizes
+/// the game currently uses. Fails if the file can't be read or isn't a level at all.
+pub fn lint_level_file(path: &Path) -> Result<Vec<LevelProblem>, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let SchemaVersion { schema } =
+        ron::from_str(&text).map_err(|err| format!("invalid level: {err}"))?;
+    // Every version so far has the same fields, but a newer one may not
+    if schema > LEVEL_SCHEMA {
+        return Ok(vec![LevelProblem {
+            severity: Severity::Error,
+            message: format!(
+                "the level uses schema {schema}, newer than the {LEVEL_SCHEMA} this version of the game knows"
+            ),
+        }]);
+    }
+
+    let level: LintedLevel = ron::from_str(&text).map_err(|err| format!("invalid level: {err}"))?;
+    let mut problems = lint_level(&level, &Tunables::load());
+    if schema < LEVEL_SCHEMA {
+        problems.push(LevelProblem {
+            severity: Severity::Warning,
+            message: format!(
+                "the level uses the old schema {schema}; saving it in the editor upgrades it to {LEVEL_SCHEMA}"
+            ),
+        });
+    }
+    Ok(problems)
+}
+
+/// Loads the manifest of the pack in the given directory and lists everything wrong with it,
+/// along with the pack's level files to check next.
+/// Fails if the manifest can't be read or isn't a manifest at all.
+pub fn lint_pack(directory: &Path) -> Result<(Vec<LevelProblem>, Vec<PathBuf>), String> {
+    let manifest = PackManifest::load(&directory.join(MANIFEST_FILE))?;
+    let mut problems = Vec::new();
+    let mut report = |severity, message: String| problems.push(LevelProblem { severity, message });
+
+    if manifest.name.trim().is_empty() {
+        report(Severity::Warning, "the pack has no name".to_string());
+    }
+    if manifest.author.trim().is_empty() {
+        report(Severity::Warning, "the pack has no author".to_string());
+    }
+    if GameVersion::parse(&manifest.version).is_none() {
+        report(
+            Severity::Error,
+            format!(
+                "the pack's version `{}` isn't major.minor.patch",
+                manifest.version
+            ),
+        );
+    }
+    match GameVersion::parse(&manifest.min_game_version) {
+        None => report(
+            Severity::Error,
+            format!(
+                "the minimum game version `{}` isn't major.minor.patch",
+                manifest.min_game_version
+            ),
+        ),
+        Some(_) if !manifest.is_supported() => report(
+            Severity::Warning,
+            format!(
+                "the pack needs version {} of the game, newer than this one",
+                manifest.min_game_version
+            ),
+        ),
+        Some(_) => {}
+    }
+    if manifest.levels.is_empty() {
+        report(Severity::Error, "the pack has no levels".to_string());
+    }
+
+    let mut paths = Vec::new();
+    for (index, level) in manifest.levels.iter().enumerate() {
+        if manifest.levels[..index].contains(level) {
+            report(
+                Severity::Warning,
+                format!("`{level}` is in the pack more than once"),
+            );
+            continue;
+        }
+        let path = directory.join(level);
+        if path.is_file() {
+            paths.push(path);
+        } else {
+            report(
+                Severity::Error,
+                format!("the level `{level}` doesn't exist"),
+            );
+        }
+    }
+
+    Ok((problems, paths))
+}
+
+fn lint_level(level: &LintedLevel, tunables: &Tunables) -> Vec<LevelProblem> {
+    let mut problems = Vec::new();
+    let mut report = |severity, message: String| problems.push(LevelProblem { severity, message });
+
+    if level.name.trim().is_empty() {
+        report(Severity::Warning, "the level has no name".to_string());
+    }
+    if level.author.trim().is_empty() {
+        report(Severity::Warning, "the level has no author".to_string());
+    }
+
+    // The game grows arenas that are too small, so check the bricks against the grown one
+    let arena = level.arena.unwrap_or_default();
+    if arena.width < MIN_ARENA_SIZE.x || arena.height < MIN_ARENA_SIZE.y {
+        report(
+            Severity::Error,
+            format!(
+                "the arena is {} by {}, smaller than the smallest playable one, {} by {}",
+                arena.width, arena.height, MIN_ARENA_SIZE.x, MIN_ARENA_SIZE.y
+            ),
+        );
+    }
+    let arena = arena.at_least_min_size();
+
+    let walls = level
+        .walls
+        .as_ref()
+        .map(|points| WallOutline::new(points.iter().map(|&(x, y)| Vec2::new(x, y)).collect()));
+    if let Some(walls) = &walls {
+        if walls.points().len() < 3 {
+            report(
+                Severity::Error,
+                "the walls need at least 3 different points to go around the arena".to_string(),
+            );
+        }
+        if walls.points().iter().any(|&point| !arena.contains(point)) {
+            report(
+                Severity::Warning,
+                "the walls go outside of the arena, where they may be off screen".to_string(),
+            );
+        }
+    }
+
+    let brick_size = tunables.brick_size;
+    let half_brick = brick_size / 2.0;
+    let left_edge = arena.left() + WALL_THICKNESS / 2.0;
+    let right_edge = arena.right() - WALL_THICKNESS / 2.0;
+    let top_edge = arena.top() - WALL_THICKNESS / 2.0;
+    let lowest_brick_edge =
+        arena.paddle_y() + tunables.paddle_size.y / 2.0 + MIN_GAP_BETWEEN_PADDLE_AND_BRICKS;
+
+    let mut kinds = Vec::with_capacity(level.bricks.len());
+    for (index, brick) in level.bricks.iter().enumerate() {
+        let position = Vec2::new(brick.position.0, brick.position.1);
+        let describe = || format!("brick #{index} at ({}, {})", position.x, position.y);
+
+        let KindName(name) = &brick.kind;
+        let kind = BrickKind::ALL
+            .into_iter()
+            .find(|kind| format!("{kind:?}") == *name);
+        if kind.is_none() {
+            report(
+                Severity::Error,
+                format!("{} has an unknown kind `{name}`", describe()),
+            );
+        }
+        kinds.push(kind);
+
+        let min = position - half_brick;
+        let max = position + half_brick;
+        if min.x < left_edge || max.x > right_edge || max.y > top_edge {
+            report(
+                Severity::Error,
+                format!("{} sticks out of the arena", describe()),
+            );
+        }
+        let corners = [min, Vec2::new(min.x, max.y), max, Vec2::new(max.x, min.y)];
+        if let Some(walls) = &walls {
+            if corners.iter().any(|&corner| !walls.contains(corner)) {
+                report(
+                    Severity::Error,
+                    format!("{} sticks out of the walls", describe()),
+                );
+            }
+        }
+        if min.y < lowest_brick_edge {
+            report(
+                Severity::Error,
+                format!("{} is too close to the paddle line", describe()),
+            );
+        }
+    }
+
+    for (index, brick) in level.bricks.iter().enumerate() {
+        let position = Vec2::new(brick.position.0, brick.position.1);
+        for (other_index, other) in level.bricks.iter().enumerate().skip(index + 1) {
+            let other_position = Vec2::new(other.position.0, other.position.1);
+            let distance = (position - other_position).abs();
+            if distance.x < brick_size.x && distance.y < brick_size.y {
+                report(
+                    Severity::Error,
+                    format!(
+                        "brick #{index} at ({}, {}) overlaps brick #{other_index} at ({}, {})",
+                        position.x, position.y, other_position.x, other_position.y
+                    ),
+                );
+            }
+        }
+    }
+
+    // Bricks of unknown kinds are already reported, don't count them either way
+    if !kinds.iter().flatten().any(|kind| kind.breakable()) {
+        report(
+            Severity::Error,
+            "the level has no breakable bricks, so it can never be cleared".to_string(),
+        );
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::Level;
+
+    // The usual grid of bricks, which breaks none of the rules
+    fn grid_level(tunables: &Tunables) -> LintedLevel {
+        LintedLevel {
+            name: "Grid".to_string(),
+            author: "Breakout team".to_string(),
+            arena: None,
+            walls: None,
+            bricks: Level::grid(tunables)
+                .bricks
+                .into_iter()
+                .map(|brick| LintedBrick {
+                    position: brick.position,
+                    kind: KindName(format!("{:?}", brick.kind)),
+                })
+                .collect(),
+        }
+    }
+
+    fn brick(x: f32, y: f32, kind: &str) -> LintedBrick {
+        LintedBrick {
+            position: (x, y),
+            kind: KindName(kind.to_string()),
+        }
+    }
+
+    // Changes the grid to break a rule, and checks the lint reports it
+    fn assert_reports(change: impl FnOnce(&mut LintedLevel), severity: Severity, message: &str) {
+        let tunables = Tunables::default();
+        let mut level = grid_level(&tunables);
+        change(&mut level);
+        let problems = lint_level(&level, &tunables);
+        assert!(
+            problems
+                .iter()
+                .any(|problem| problem.severity == severity && problem.message.contains(message)),
+            "expected {severity:?} `{message}`, got {problems:?}"
+        );
+    }
+
+    #[test]
+    fn grid_has_no_problems() {
+        let tunables = Tunables::default();
+        let problems = lint_level(&grid_level(&tunables), &tunables);
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn reports_missing_name() {
+        assert_reports(
+            |level| level.name = " ".to_string(),
+            Severity::Warning,
+            "the level has no name",
+        );
+    }
+
+    #[test]
+    fn reports_missing_author() {
+        assert_reports(
+            |level| level.author.clear(),
+            Severity::Warning,
+            "the level has no author",
+        );
+    }
+
+    #[test]
+    fn reports_arena_too_small() {
+        assert_reports(
+            |level| {
+                level.arena = Some(Arena {
+                    width: 10.0,
+                    height: 10.0,
+                })
+            },
+            Severity::Error,
+            "smaller than the smallest playable one",
+        );
+    }
+
+    #[test]
+    fn reports_too_few_wall_points() {
+        assert_reports(
+            |level| level.walls = Some(vec![(-400.0, -250.0), (400.0, -250.0)]),
+            Severity::Error,
+            "at least 3 different points",
+        );
+    }
+
+    #[test]
+    fn reports_walls_outside_of_the_arena() {
+        assert_reports(
+            |level| {
+                level.walls = Some(vec![
+                    (-2000.0, -2000.0),
+                    (2000.0, -2000.0),
+                    (2000.0, 2000.0),
+                    (-2000.0, 2000.0),
+                ])
+            },
+            Severity::Warning,
+            "the walls go outside of the arena",
+        );
+    }
+
+    #[test]
+    fn reports_unknown_kinds() {
+        assert_reports(
+            |level| level.bricks[0].kind = KindName("Glass".to_string()),
+            Severity::Error,
+            "has an unknown kind `Glass`",
+        );
+    }
+
+    #[test]
+    fn reports_bricks_outside_of_the_arena() {
+        assert_reports(
+            |level| {
+                level
+                    .bricks
+                    .push(brick(Arena::default().right(), 0.0, "Normal"))
+            },
+            Severity::Error,
+            "sticks out of the arena",
+        );
+    }
+
+    #[test]
+    fn reports_bricks_outside_of_the_walls() {
+        // A triangle in the bottom left corner, away from all the bricks
+        assert_reports(
+            |level| level.walls = Some(vec![(-440.0, -290.0), (0.0, -290.0), (-440.0, 0.0)]),
+            Severity::Error,
+            "sticks out of the walls",
+        );
+    }
+
+    #[test]
+    fn reports_bricks_close_to_the_paddle() {
+        assert_reports(
+            |level| {
+                level
+                    .bricks
+                    .push(brick(0.0, Arena::default().paddle_y(), "Normal"))
+            },
+            Severity::Error,
+            "is too close to the paddle line",
+        );
+    }
+
+    #[test]
+    fn reports_overlapping_bricks() {
+        assert_reports(
+            |level| {
+                let (x, y) = level.bricks[0].position;
+                level.bricks.push(brick(x + 1.0, y, "Normal"));
+            },
+            Severity::Error,
+            "overlaps brick #",
+        );
+    }
+
+    #[test]
+    fn reports_levels_that_cant_be_cleared() {
+        assert_reports(
+            |level| {
+                for brick in &mut level.bricks {
+                    brick.kind = KindName("Unbreakable".to_string());
+                }
+            },
+            Severity::Error,
+            "the level has no breakable bricks",
+        );
+    }
+
+    #[test]
+    fn reads_brick_kinds_as_written_in_level_files() {
+        let level: LintedLevel = ron::from_str(
+            "(bricks: [(position: (0.0, 0.0), kind: Tough), (position: (60.0, 0.0), kind: Golden), (position: (120.0, 0.0))])",
+        )
+        .unwrap();
+        let kinds: Vec<&str> = level
+            .bricks
+            .iter()
+            .map(|brick| brick.kind.0.as_str())
+            .collect();
+        assert_eq!(kinds, ["Tough", "Golden", "Normal"]);
+    }
+}
@@ -0,0 +1,497 @@
+//! Two-player games across a LAN, kept in sync with deterministic lockstep.
+//!
+//! The peers only exchange paddle inputs over UDP. Each one simulates a fixed step once
+//! it knows both players' inputs for that step, so both run the exact same game.
+//! Inputs are scheduled a few steps ahead of time to hide the network latency,
+//! and the peers regularly compare checksums of their game state to catch desyncs.
+//!
+//! With `--net-rollback`, a peer doesn't wait for the other player's inputs. It guesses them
+//! and goes on, and rolls the game back to fix things up once the real inputs come in.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use crate::{
+    arg_value, versus::VersusScores, Ball, Brick, Paddle, PaddleInput, Player, PlayerControlled,
+    Scoreboard, Velocity, PLAYER_ONE_KEYS,
+};
+
+// How many steps ahead local inputs are scheduled
+const INPUT_DELAY: u32 = 4;
+// Rollback hides most of the latency, so the inputs can take effect a lot sooner
+const ROLLBACK_INPUT_DELAY: u32 = 1;
+// How far a peer may run ahead of the inputs it has received, with rollback.
+// Anything beyond that would be too much to simulate again when a guess turns out wrong.
+const MAX_PREDICTED_STEPS: u32 = 8;
+// Every packet carries this many of the latest local inputs, so a lost packet doesn't matter
+const INPUT_WINDOW: u32 = 32;
+// How often the peers compare their game state, in steps
+const CHECKSUM_INTERVAL: u32 = 30;
+const MAX_PACKET_SIZE: usize = 512;
+// Marks packets without a checksum
+const NO_CHECKSUM: u32 = u32::MAX;
+
+/// Network settings, from `--net-bind <address> --net-peer <address> --net-player <one|two>`,
+/// plus `--net-rollback` to predict the other player's inputs instead of waiting for them
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct LockstepConfig {
+    bind: SocketAddr,
+    peer: SocketAddr,
+    local_player: Player,
+    pub(crate) rollback: bool,
+}
+
+impl LockstepConfig {
+    pub(crate) fn from_args() -> Option<Self> {
+        let bind = arg_value("--net-bind")?;
+        let peer = arg_value("--net-peer")?;
+        let local_player = match arg_value("--net-player").as_deref() {
+            Some("two") => Player::Two,
+            _ => Player::One,
+        };
+
+        let parse = |address: String| {
+            address
+                .parse()
+                .unwrap_or_else(|err| panic!("Invalid network address {address:?}: {err}"))
+        };
+        Some(LockstepConfig {
+            bind: parse(bind),
+            peer: parse(peer),
+            local_player,
+            rollback: std::env::args().any(|arg| arg == "--net-rollback"),
+        })
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct Lockstep {
+    socket: UdpSocket,
+    local_player: Player,
+    input_delay: u32,
+    // The next step to simulate
+    tick: u32,
+    local_inputs: BTreeMap<u32, i8>,
+    remote_inputs: BTreeMap<u32, i8>,
+    // With rollback: the guesses made for remote inputs that haven't arrived yet
+    rollback: bool,
+    predicted_inputs: BTreeMap<u32, i8>,
+    // Checksums waiting for the other peer's checksum of the same step
+    local_checksums: BTreeMap<u32, u64>,
+    remote_checksums: BTreeMap<u32, u64>,
+    desynced: bool,
+}
+
+impl Lockstep {
+    pub(crate) fn connect(config: LockstepConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(config.bind)?;
+        socket.connect(config.peer)?;
+        socket.set_nonblocking(true)?;
+
+        let input_delay = if config.rollback {
+            ROLLBACK_INPUT_DELAY
+        } else {
+            INPUT_DELAY
+        };
+        // Nobody has had the chance to press anything during the first steps
+        let idle: BTreeMap<u32, i8> = (0..input_delay).map(|tick| (tick, 0)).collect();
+
+        Ok(Lockstep {
+            socket,
+            local_player: config.local_player,
+            input_delay,
+            tick: 0,
+            local_inputs: idle.clone(),
+            remote_inputs: idle,
+            rollback: config.rollback,
+            predicted_inputs: BTreeMap::new(),
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            desynced: false,
+        })
+    }
+
+    fn inputs_ready(&self) -> bool {
+        if !self.local_inputs.contains_key(&self.tick) {
+            return false;
+        }
+        if self.rollback {
+            self.tick < self.confirmed_steps() + MAX_PREDICTED_STEPS
+        } else {
+            self.remote_inputs.contains_key(&self.tick)
+        }
+    }
+
+    /// The next step to simulate
+    pub(crate) fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Go back to an earlier step, to simulate it again
+    pub(crate) fn rewind(&mut self, tick: u32) {
+        self.tick = tick;
+    }
+
+    /// The number of steps for which both players' inputs are known.
+    /// The state at the start of the next one can't change anymore.
+    pub(crate) fn confirmed_steps(&self) -> u32 {
+        // Packets carry all recent inputs, so there are no holes before the latest one
+        self.remote_inputs
+            .keys()
+            .next_back()
+            .map_or(0, |&tick| tick + 1)
+    }
+
+    /// Forgets about the guesses that have been confirmed or refuted by the actual inputs,
+    /// and returns the first step that was simulated with a wrong guess
+    pub(crate) fn take_misprediction(&mut self) -> Option<u32> {
+        let mut mispredicted = None;
+        let arrived: Vec<u32> = self
+            .predicted_inputs
+            .keys()
+            .filter(|tick| self.remote_inputs.contains_key(tick))
+            .copied()
+            .collect();
+        for tick in arrived {
+            let guess = self.predicted_inputs.remove(&tick);
+            if guess != self.remote_inputs.get(&tick).copied() && mispredicted.is_none() {
+                mispredicted = Some(tick);
+            }
+        }
+        mispredicted
+    }
+
+    /// Adds the local checksum of the state at the start of a step, as it is simulated.
+    /// With rollback, the state may still change when the actual remote inputs come in,
+    /// so its checksums are only taken once it can't anymore, see `record_confirmed_checksum`.
+    pub(crate) fn record_step_checksum(&mut self, tick: u32, checksum: u64) {
+        if !self.rollback {
+            self.record_confirmed_checksum(tick, checksum);
+        }
+    }
+
+    /// Adds the local checksum of the state at the start of a step that won't be simulated again
+    pub(crate) fn record_confirmed_checksum(&mut self, tick: u32, checksum: u64) {
+        if tick.is_multiple_of(CHECKSUM_INTERVAL) {
+            self.local_checksums.insert(tick, checksum);
+            self.check_for_desync();
+        }
+    }
+
+    fn send(&self) {
+        let first_tick = self.tick.saturating_sub(INPUT_WINDOW);
+        let inputs: Vec<(u32, i8)> = self
+            .local_inputs
+            .range(first_tick..)
+            .map(|(&tick, &input)| (tick, input))
+            .collect();
+        let checksum = self
+            .local_checksums
+            .iter()
+            .next_back()
+            .map(|(&tick, &checksum)| (tick, checksum));
+
+        let packet = encode_packet(&inputs, checksum);
+        match self.socket.send(&packet) {
+            Ok(_) => {}
+            // Nothing is listening on the other end yet
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => warn!("Could not send inputs to the other player: {err}"),
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            let len = match self.socket.recv(&mut buffer) {
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => return,
+                Err(err) => {
+                    warn!("Could not receive inputs from the other player: {err}");
+                    return;
+                }
+            };
+
+            let Some((inputs, checksum)) = decode_packet(&buffer[..len]) else {
+                warn!("Ignoring a malformed packet from the other player");
+                continue;
+            };
+
+            let oldest = self.tick.saturating_sub(INPUT_WINDOW);
+            for (tick, input) in inputs {
+                if tick >= oldest {
+                    self.remote_inputs.insert(tick, input);
+                }
+            }
+            if let Some((tick, checksum)) = checksum {
+                self.remote_checksums.insert(tick, checksum);
+            }
+        }
+    }
+
+    // Compare the checksums both peers have for the same step
+    fn check_for_desync(&mut self) {
+        let common: Vec<u32> = self
+            .local_checksums
+            .keys()
+            .filter(|tick| self.remote_checksums.contains_key(tick))
+            .copied()
+            .collect();
+
+        for tick in common {
+            let local = self.local_checksums[&tick];
+            let remote = self.remote_checksums.remove(&tick);
+            if remote != Some(local) && !self.desynced {
+                error!(
+                    "The game went out of sync with the other player at step {tick} \
+                     (local checksum {local:016x}, remote {:016x})",
+                    remote.unwrap_or_default()
+                );
+                self.desynced = true;
+            }
+        }
+
+        // Keep the latest local checksum around, it goes out with every packet
+        let oldest = self.tick.saturating_sub(INPUT_WINDOW);
+        self.local_checksums = self.local_checksums.split_off(&oldest);
+        self.remote_checksums = self.remote_checksums.split_off(&oldest);
+    }
+}
+
+// Pipe the fixed timestep into this to hold the simulation back until both inputs are in
+pub(crate) fn wait_for_inputs(
+    In(should_run): In<ShouldRun>,
+    mut lockstep: ResMut<Lockstep>,
+) -> ShouldRun {
+    // Keep talking even while stalled, or the peers would wait for each other forever
+    lockstep.receive();
+    lockstep.send();
+
+    if lockstep.desynced || !lockstep.inputs_ready() {
+        return ShouldRun::No;
+    }
+    should_run
+}
+
+// The local paddle is steered with the arrow keys, the other one by the network
+pub(crate) fn setup_lockstep(
+    mut commands: Commands,
+    lockstep: Res<Lockstep>,
+    paddle_query: Query<(Entity, &Player), With<Paddle>>,
+) {
+    for (paddle, player) in &paddle_query {
+        if *player == lockstep.local_player {
+            commands.entity(paddle).insert(PLAYER_ONE_KEYS);
+        } else {
+            commands.entity(paddle).remove::<PlayerControlled>();
+        }
+    }
+}
+
+// Runs at the start of every step: records the state the step starts from,
+// queues up the local input and feeds both paddles the inputs scheduled for this step
+pub(crate) fn apply_inputs(
+    mut lockstep: ResMut<Lockstep>,
+    scoreboard: Res<Scoreboard>,
+    versus_scores: Option<Res<VersusScores>>,
+    ball_query: Query<(&Transform, &Velocity), With<Ball>>,
+    brick_query: Query<(), With<Brick>>,
+    mut paddle_query: Query<(&Transform, &Player, &mut PaddleInput), With<Paddle>>,
+) {
+    let tick = lockstep.tick;
+
+    if !lockstep.rollback && tick.is_multiple_of(CHECKSUM_INTERVAL) {
+        let mut checksum = Checksum::default();
+        checksum.write_u64(tick as u64);
+        for (transform, velocity) in &ball_query {
+            checksum.write_vec2(transform.translation.truncate());
+            checksum.write_vec2(velocity.0);
+        }
+        let mut paddles: Vec<(Player, f32)> = paddle_query
+            .iter()
+            .map(|(transform, player, _)| (*player, transform.translation.x))
+            .collect();
+        paddles.sort_by_key(|(player, _)| player.index());
+        for (_, x) in paddles {
+            checksum.write_f32(x);
+        }
+        checksum.write_u64(brick_query.iter().count() as u64);
+        checksum.write_u64(scoreboard.score as u64);
+        checksum.write_u64(scoreboard.lives as u64);
+        if let Some(scores) = versus_scores {
+            checksum.write_u64(scores.points(Player::One) as u64);
+            checksum.write_u64(scores.points(Player::Two) as u64);
+        }
+
+        lockstep.record_step_checksum(tick, checksum.finish());
+    }
+
+    // Whatever the local player is doing right now only takes effect a few steps later.
+    // When a step is simulated again, the input recorded the first time around still stands.
+    let local_player = lockstep.local_player;
+    if let Some((_, _, input)) = paddle_query
+        .iter()
+        .find(|(_, player, _)| **player == local_player)
+    {
+        let input = quantize(input.0);
+        let scheduled_tick = tick + lockstep.input_delay;
+        lockstep.local_inputs.entry(scheduled_tick).or_insert(input);
+    }
+
+    let remote_input = match lockstep.remote_inputs.get(&tick) {
+        Some(&input) => input,
+        // Guess that the other player keeps doing whatever they did last
+        None => {
+            let guess = lockstep
+                .remote_inputs
+                .range(..tick)
+                .next_back()
+                .map(|(_, &input)| input)
+                .unwrap_or_default();
+            lockstep.predicted_inputs.insert(tick, guess);
+            guess
+        }
+    };
+    let local_input = lockstep.local_inputs.get(&tick).copied().unwrap_or_default();
+    for (_, player, mut input) in &mut paddle_query {
+        input.0 = if *player == local_player {
+            dequantize(local_input)
+        } else {
+            dequantize(remote_input)
+        };
+    }
+
+    let oldest = tick.saturating_sub(INPUT_WINDOW);
+    lockstep.local_inputs = lockstep.local_inputs.split_off(&oldest);
+    lockstep.remote_inputs = lockstep.remote_inputs.split_off(&oldest);
+    lockstep.predicted_inputs = lockstep.predicted_inputs.split_off(&oldest);
+    lockstep.tick += 1;
+}
+
+// Inputs travel as a single byte. Both peers use the rounded value,
+// including for their own paddle, so they stay in agreement.
+fn quantize(input: f32) -> i8 {
+    (input.clamp(-1.0, 1.0) * i8::MAX as f32).round() as i8
+}
+
+fn dequantize(input: i8) -> f32 {
+    input as f32 / i8::MAX as f32
+}
+
+// Packet layout, little endian:
+// first tick (u32), input count (u8), the inputs (i8 each),
+// checksum tick (u32, `NO_CHECKSUM` if absent), checksum (u64)
+fn encode_packet(inputs: &[(u32, i8)], checksum: Option<(u32, u64)>) -> Vec<u8> {
+    let first_tick = inputs.first().map(|(tick, _)| *tick).unwrap_or_default();
+    let (checksum_tick, checksum) = checksum.unwrap_or((NO_CHECKSUM, 0));
+
+    let mut packet = Vec::with_capacity(4 + 1 + inputs.len() + 4 + 8);
+    packet.extend_from_slice(&first_tick.to_le_bytes());
+    packet.push(inputs.len() as u8);
+    packet.extend(inputs.iter().map(|(_, input)| *input as u8));
+    packet.extend_from_slice(&checksum_tick.to_le_bytes());
+    packet.extend_from_slice(&checksum.to_le_bytes());
+    packet
+}
+
+type Packet = (Vec<(u32, i8)>, Option<(u32, u64)>);
+
+fn decode_packet(packet: &[u8]) -> Option<Packet> {
+    let first_tick = u32::from_le_bytes(packet.get(0..4)?.try_into().ok()?);
+    let count = *packet.get(4)? as usize;
+    let inputs = packet.get(5..5 + count)?;
+    let rest = &packet[5 + count..];
+    let checksum_tick = u32::from_le_bytes(rest.get(0..4)?.try_into().ok()?);
+    let checksum = u64::from_le_bytes(rest.get(4..12)?.try_into().ok()?);
+
+    // Garbage may claim ticks past the last one there can be
+    let inputs = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, &input)| Some((first_tick.checked_add(i as u32)?, input as i8)))
+        .collect::<Option<_>>()?;
+    let checksum = (checksum_tick != NO_CHECKSUM).then_some((checksum_tick, checksum));
+    Some((inputs, checksum))
+}
+
+/// 64-bit FNV-1a over the exact bits of the game state
+pub(crate) struct Checksum(u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Checksum {
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_bits().to_le_bytes());
+    }
+
+    pub(crate) fn write_vec2(&mut self, value: Vec2) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_round_trip() {
+        let inputs = vec![(40, -127), (41, 0), (42, 127)];
+        for checksum in [Some((30, 0xdead_beef_0123_4567)), None] {
+            let packet = encode_packet(&inputs, checksum);
+            assert_eq!(decode_packet(&packet), Some((inputs.clone(), checksum)));
+        }
+    }
+
+    #[test]
+    fn packets_without_inputs_round_trip() {
+        let packet = encode_packet(&[], Some((0, 1)));
+        assert_eq!(decode_packet(&packet), Some((Vec::new(), Some((0, 1)))));
+    }
+
+    #[test]
+    fn short_packets_are_rejected() {
+        let packet = encode_packet(&[(7, 1), (8, 2)], Some((0, 3)));
+        for length in 0..packet.len() {
+            assert_eq!(decode_packet(&packet[..length]), None, "{length} bytes");
+        }
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        // Claims more inputs than there are bytes
+        assert_eq!(decode_packet(&[0, 0, 0, 0, 200, 1, 2, 3]), None);
+
+        // Inputs running past the last tick there can be
+        let mut packet = vec![0xff, 0xff, 0xff, 0xff, 2, 0, 0];
+        packet.extend_from_slice(&NO_CHECKSUM.to_le_bytes());
+        packet.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(decode_packet(&packet), None);
+    }
+}
@@ -0,0 +1,438 @@
+//! Level packs: a directory of levels played one after the other, started with `--packs`.
+//!
+//! Every directory in `assets/levels` with a `pack.ron` manifest is a pack:
+//!
+//! ```ron
+//! (
+//!     schema: 1,
+//!     name: "First Steps",
+//!     author: "Someone",
+//!     version: "1.0.0",
+//!     min_game_version: "0.1.0",
+//!     levels: ["warm-up.ron", "pillars.ron"],
+//! )
+//! ```
+//!
+//! Level paths are relative to the pack's directory.
+//! Packs made for a newer version of the game are listed, but can't be picked.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::Arena,
+    level::{Level, SchemaVersion},
+    tunables::Tunables,
+    Ball, Brick, BrickAssets, GameMode, GameOverText, PaddleFilter, PaddleLane, RoundState,
+    Scoreboard, Velocity, SCOREBOARD_FONT_SIZE, SCOREBOARD_TEXT_PADDING, STARTING_LIVES,
+};
+
+/// The version of the format pack manifests are written in
+pub(crate) const PACK_SCHEMA: u32 = 1;
+
+pub const LEVELS_DIRECTORY: &str = "assets/levels";
+pub const MANIFEST_FILE: &str = "pack.ron";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PackManifest {
+    pub(crate) schema: u32,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) author: String,
+    /// The pack's own version
+    pub(crate) version: String,
+    /// The oldest version of the game the pack can be played with
+    pub(crate) min_game_version: String,
+    /// In the order they are played
+    pub(crate) levels: Vec<String>,
+}
+
+impl PackManifest {
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let SchemaVersion { schema } =
+            ron::from_str(&text).map_err(|err| format!("invalid pack manifest: {err}"))?;
+        if schema > PACK_SCHEMA {
+            return Err(format!(
+                "the pack uses schema {schema}, but this version of the game only knows up to {PACK_SCHEMA}"
+            ));
+        }
+        ron::from_str(&text).map_err(|err| format!("invalid pack manifest: {err}"))
+    }
+
+    /// Whether this version of the game is recent enough for the pack
+    pub(crate) fn is_supported(&self) -> bool {
+        match (
+            GameVersion::parse(&self.min_game_version),
+            GameVersion::parse(env!("CARGO_PKG_VERSION")),
+        ) {
+            (Some(required), Some(current)) => required <= current,
+            _ => false,
+        }
+    }
+}
+
+/// A `major.minor.patch` version number
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) struct GameVersion(u64, u64, u64);
+
+impl GameVersion {
+    pub(crate) fn parse(version: &str) -> Option<Self> {
+        let mut numbers = version.trim().split('.').map(|number| number.parse().ok());
+        let version = GameVersion(numbers.next()??, numbers.next()??, numbers.next()??);
+        numbers.next().is_none().then_some(version)
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct LevelPack {
+    pub(crate) directory: PathBuf,
+    pub(crate) manifest: PackManifest,
+}
+
+impl LevelPack {
+    fn load_level(&self, index: usize) -> Result<Level, String> {
+        let path = self.directory.join(&self.manifest.levels[index]);
+        Level::load(&path).map_err(|err| format!("Could not load {}: {err}", path.display()))
+    }
+}
+
+/// Directories in `root` with a manifest, sorted by directory name.
+/// Along with the packs, the manifests that could not be read and why.
+pub(crate) fn discover_packs(root: &Path) -> (Vec<LevelPack>, Vec<(PathBuf, String)>) {
+    let mut directories: Vec<PathBuf> = match fs::read_dir(root) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join(MANIFEST_FILE).is_file())
+            .collect(),
+        Err(err) => return (Vec::new(), vec![(root.to_path_buf(), err.to_string())]),
+    };
+    directories.sort();
+
+    let mut packs = Vec::new();
+    let mut errors = Vec::new();
+    for directory in directories {
+        let path = directory.join(MANIFEST_FILE);
+        match PackManifest::load(&path) {
+            Ok(manifest) => packs.push(LevelPack {
+                directory,
+                manifest,
+            }),
+            Err(err) => errors.push((path, err)),
+        }
+    }
+    (packs, errors)
+}
+
+/// The packs to choose from, and how far into the chosen one the player is
+#[derive(Resource)]
+pub(crate) struct PackSelect {
+    packs: Vec<LevelPack>,
+    selected: usize,
+    // Which level of the selected pack is being played, once it has been picked
+    level: Option<usize>,
+    // Why the last pick didn't work out, if it didn't
+    status: String,
+}
+
+impl PackSelect {
+    pub(crate) fn discover() -> Self {
+        let (packs, errors) = discover_packs(Path::new(LEVELS_DIRECTORY));
+        for (path, err) in &errors {
+            warn!("Skipping the level pack {}: {err}", path.display());
+        }
+        PackSelect {
+            packs,
+            selected: 0,
+            level: None,
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct PackSelectText;
+
+pub(crate) fn setup_pack_select(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    tunables: Res<Tunables>,
+) {
+    let style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: SCOREBOARD_FONT_SIZE / 2.0,
+        color: tunables.colors.text,
+    };
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::from_style(TextStyle {
+                font_size: SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+                ..style.clone()
+            }),
+            TextSection::from_style(style),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: SCOREBOARD_TEXT_PADDING,
+                left: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+            ..default()
+        }),
+        PackSelectText,
+    ));
+}
+
+// Up and down pick a pack, Enter starts playing it
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn choose_pack(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    keyboard_input: Res<Input<KeyCode>>,
+    tunables: Res<Tunables>,
+    mut select: ResMut<PackSelect>,
+    mut round: ResMut<RoundState>,
+    mut scoreboard: ResMut<Scoreboard>,
+) {
+    if *round != RoundState::SelectingPack || select.packs.is_empty() {
+        return;
+    }
+
+    let count = select.packs.len();
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        select.selected = (select.selected + 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        select.selected = (select.selected + count - 1) % count;
+    }
+    if !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    let pack = &select.packs[select.selected];
+    if !pack.manifest.is_supported() {
+        select.status = format!(
+            "{} needs version {} of the game",
+            pack.manifest.name, pack.manifest.min_game_version
+        );
+        return;
+    }
+    if pack.manifest.levels.is_empty() {
+        select.status = format!("{} has no levels", pack.manifest.name);
+        return;
+    }
+    match pack.load_level(0) {
+        Ok(level) => {
+            level.spawn_bricks(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut brick_assets,
+                &tunables,
+            );
+            commands.insert_resource(level.arena());
+            commands.insert_resource(level.walls());
+            commands.insert_resource(level);
+            scoreboard.score = 0;
+            scoreboard.lives = STARTING_LIVES;
+            select.level = Some(0);
+            select.status.clear();
+            *round = RoundState::Playing;
+        }
+        Err(err) => select.status = err,
+    }
+}
+
+// Once a level is over, Enter moves on to the next one of the pack,
+// or back to picking a pack after the last one or a game over
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn continue_pack(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mode: Res<GameMode>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut select: ResMut<PackSelect>,
+    mut round: ResMut<RoundState>,
+    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+    mut paddle_query: Query<(&mut Transform, &PaddleLane), PaddleFilter>,
+    brick_query: Query<Entity, With<Brick>>,
+    game_over_query: Query<Entity, With<GameOverText>>,
+) {
+    let over = matches!(*round, RoundState::Cleared | RoundState::GameOver);
+    if !over || !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+    let Some(current) = select.level else {
+        return;
+    };
+
+    for entity in brick_query.iter().chain(&game_over_query) {
+        commands.entity(entity).despawn();
+    }
+    for (mut transform, mut velocity) in &mut ball_query {
+        transform.translation = mode.ball_starting_position(&tunables, &arena);
+        velocity.0 = tunables.serve_velocity();
+    }
+    for (mut transform, lane) in &mut paddle_query {
+        transform.translation.x = lane.center();
+    }
+
+    // Score and lives carry over from one level to the next
+    let next = current + 1;
+    let pack = &select.packs[select.selected];
+    if *round == RoundState::Cleared && next < pack.manifest.levels.len() {
+        match pack.load_level(next) {
+            Ok(level) => {
+                level.spawn_bricks(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    &mut brick_assets,
+                    &tunables,
+                );
+                // The walls and paddles move over to the new level's arena
+                commands.insert_resource(level.arena());
+                commands.insert_resource(level.walls());
+                commands.insert_resource(level);
+                select.level = Some(next);
+                *round = RoundState::Playing;
+                return;
+            }
+            Err(err) => select.status = err,
+        }
+    }
+
+    select.level = None;
+    *round = RoundState::SelectingPack;
+}
+
+pub(crate) fn update_pack_select_text(
+    round: Res<RoundState>,
+    select: Res<PackSelect>,
+    level: Option<Res<Level>>,
+    mut query: Query<&mut Text, With<PackSelectText>>,
+) {
+    let level_changed = level.as_ref().is_some_and(|level| level.is_changed());
+    if !(round.is_changed() || select.is_changed() || level_changed) {
+        return;
+    }
+
+    let (title, body) = match (*round, select.level) {
+        (RoundState::SelectingPack, _) if select.packs.is_empty() => (
+            "No level packs".to_string(),
+            format!("Add some to {LEVELS_DIRECTORY}"),
+        ),
+        (RoundState::SelectingPack, _) => {
+            let mut body = String::new();
+            for (index, pack) in select.packs.iter().enumerate() {
+                let manifest = &pack.manifest;
+                let marker = if index == select.selected {
+                    "> "
+                } else {
+                    "   "
+                };
+                let note = if manifest.is_supported() {
+                    format!("{} levels", manifest.levels.len())
+                } else {
+                    format!("needs game {}", manifest.min_game_version)
+                };
+                body.push_str(&format!(
+                    "{marker}{} v{} by {} ({note})\n",
+                    manifest.name, manifest.version, manifest.author
+                ));
+            }
+            body.push_str("\nUp / Down: pick a pack   Enter: play\n");
+            body.push_str(&select.status);
+            ("Level Packs\n".to_string(), body)
+        }
+        // The level's name shows until the round is over, along with what Enter does then
+        (_, Some(index)) => {
+            let pack = &select.packs[select.selected];
+            let name = level.map_or(String::new(), |level| level.name.clone());
+            let title = format!(
+                "{} {}/{}: {name}\n",
+                pack.manifest.name,
+                index + 1,
+                pack.manifest.levels.len()
+            );
+            let body = match *round {
+                RoundState::Cleared if index + 1 < pack.manifest.levels.len() => {
+                    "Enter: next level".to_string()
+                }
+                RoundState::Cleared | RoundState::GameOver => {
+                    "Enter: back to the packs".to_string()
+                }
+                _ => String::new(),
+            };
+            (title, body)
+        }
+        _ => (String::new(), String::new()),
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = title.clone();
+        text.sections[1].value = body.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{BrickKind, LevelBrick, LEVEL_SCHEMA};
+
+    // A pack with a level saved by the first version of the editor, before there was a schema
+    const OLD_FORMAT_PACKS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/packs");
+
+    #[test]
+    fn migrates_levels_in_the_first_format() {
+        let (packs, errors) = discover_packs(Path::new(OLD_FORMAT_PACKS));
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(packs.len(), 1);
+
+        let level = packs[0].load_level(0).unwrap();
+        assert_eq!(level.schema, LEVEL_SCHEMA);
+        assert_eq!(level.name, "First Editor");
+        assert_eq!(level.author, "Breakout team");
+        // Levels from back then were all played in the usual arena, between its sides
+        assert_eq!(level.arena, None);
+        assert_eq!(level.walls, None);
+        assert_eq!(level.arena(), Arena::default().at_least_min_size());
+        assert_eq!(
+            level.bricks,
+            vec![
+                LevelBrick {
+                    position: (-120.0, 147.5),
+                    kind: BrickKind::Normal,
+                },
+                LevelBrick {
+                    position: (0.0, 147.5),
+                    kind: BrickKind::Tough,
+                },
+                LevelBrick {
+                    position: (120.0, 147.5),
+                    kind: BrickKind::Unbreakable,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! The `rapier` feature: hands the ball's movement and what it runs into over to the rapier
+//! physics engine, instead of the game's own collision tests.
+//!
+//! The game keeps working with its own `Collider`s and `Velocity`s; every step, they are handed
+//! to rapier as bodies, rapier moves the ball and bounces it off things, and what it runs into
+//! comes back as the usual `CollisionEvent`s, brick hits and lost balls.
+//! Paddles are kinematic bodies moved by the game, the ball is a dynamic body that keeps all of
+//! its speed when it bounces, walls and bricks are fixed, and loss zones are sensors the ball
+//! goes right through.
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude as rapier;
+use bevy_rapier2d::prelude::{NoUserData, PhysicsStages, RapierPhysicsPlugin};
+
+use crate::{
+    collision::Collider, tunables::Tunables, Ball, BallLostEvent, BrickAssets, BrickDestroyedEvent,
+    BrickHitEvent, BrickKind, CollisionEvent, LossZone, Obstacle, Paddle, PaddleHitEvent,
+    Scoreboard, Velocity, TIME_STEP,
+};
+
+// Rapier works best with things around a meter in size, and a brick is about a meter wide
+const PIXELS_PER_METER: f32 = 100.0;
+
+/// The part of a game step rapier takes care of. It is run by `run_game_step`, right before
+/// the rest of the step, so that paddles, served balls and restored snapshots are picked up.
+#[derive(Resource)]
+struct PhysicsStep(Schedule);
+
+#[derive(StageLabel)]
+enum PhysicsStage {
+    // Turns new or changed colliders into bodies, and hands the velocities over
+    Prepare,
+    // Rapier's own stages, in order
+    SyncBackend,
+    StepSimulation,
+    Writeback,
+    // Takes the velocities back, once rapier bounced the ball around
+    Finish,
+    DetectDespawn,
+}
+
+pub(crate) fn add_physics(app: &mut App) {
+    // Rapier's systems are run as part of the game step rather than every frame,
+    // which keeps networked games and rollbacks stepping in lockstep
+    app.add_plugin(
+        RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PIXELS_PER_METER)
+            .with_default_system_setup(false),
+    )
+    .insert_resource(rapier::RapierConfiguration {
+        gravity: Vec2::ZERO,
+        timestep_mode: rapier::TimestepMode::Fixed {
+            dt: TIME_STEP,
+            substeps: 1,
+        },
+        ..default()
+    });
+
+    let rapier_stage = |stage| {
+        SystemStage::single_threaded()
+            .with_system_set(RapierPhysicsPlugin::<NoUserData>::get_systems(stage))
+    };
+    let mut schedule = Schedule::default();
+    schedule
+        .add_stage(
+            PhysicsStage::Prepare,
+            SystemStage::single_threaded()
+                .with_system(attach_bodies)
+                .with_system(push_velocities),
+        )
+        // Also brings the global transforms rapier reads up to date
+        .add_stage(
+            PhysicsStage::SyncBackend,
+            rapier_stage(PhysicsStages::SyncBackend),
+        )
+        .add_stage(
+            PhysicsStage::StepSimulation,
+            rapier_stage(PhysicsStages::StepSimulation),
+        )
+        .add_stage(
+            PhysicsStage::Writeback,
+            rapier_stage(PhysicsStages::Writeback),
+        )
+        .add_stage(
+            PhysicsStage::Finish,
+            SystemStage::single_threaded().with_system(pull_velocities),
+        )
+        .add_stage(
+            PhysicsStage::DetectDespawn,
+            rapier_stage(PhysicsStages::DetectDespawn),
+        );
+    app.insert_resource(PhysicsStep(schedule));
+}
+
+// Takes the physics part of a single step of the game
+pub(crate) fn run_physics_step(world: &mut World) {
+    world.resource_scope(|world, mut step: Mut<PhysicsStep>| step.0.run(world));
+}
+
+fn shape(collider: &Collider) -> rapier::Collider {
+    match *collider {
+        Collider::Circle(radius) => rapier::Collider::ball(radius),
+        // Rapier turns boxes along with their transform either way
+        Collider::Aabb(size) | Collider::OrientedBox(size) => {
+            rapier::Collider::cuboid(size.x / 2.0, size.y / 2.0)
+        }
+        Collider::Segment(segment) => rapier::Collider::segment(segment.start, segment.end),
+    }
+}
+
+// A new collider, and what kind of body it needs
+type BodyParts = (
+    Entity,
+    Option<&'static Velocity>,
+    Option<&'static Ball>,
+    Option<&'static Paddle>,
+    Option<&'static LossZone>,
+);
+
+// Gives everything with a collider a body, and its shape again whenever the collider changes,
+// e.g. when paddles are resized by the tunables. The body is only set up once, so that
+// the ball keeps its velocity when it is resized.
+fn attach_bodies(
+    mut commands: Commands,
+    added_query: Query<BodyParts, Added<Collider>>,
+    changed_query: Query<(Entity, &Collider), Changed<Collider>>,
+) {
+    for (entity, maybe_velocity, maybe_ball, maybe_paddle, maybe_loss_zone) in &added_query {
+        let mut entity = commands.entity(entity);
+        entity.insert((
+            rapier::Friction {
+                coefficient: 0.0,
+                combine_rule: rapier::CoefficientCombineRule::Min,
+            },
+            // Bounces never slow the ball down, whatever it hits
+            rapier::Restitution {
+                coefficient: 1.0,
+                combine_rule: rapier::CoefficientCombineRule::Max,
+            },
+        ));
+
+        if maybe_ball.is_some() {
+            // Rapier takes the velocity when it makes the body, after `push_velocities` ran
+            let velocity = maybe_velocity.map_or(Vec2::ZERO, |velocity| velocity.0);
+            entity.insert((
+                rapier::RigidBody::Dynamic,
+                rapier::Velocity::linear(velocity),
+                rapier::GravityScale(0.0),
+                rapier::LockedAxes::ROTATION_LOCKED,
+                // The ball is small and fast, so make sure it can't skip through a wall
+                rapier::Ccd::enabled(),
+                rapier::ActiveEvents::COLLISION_EVENTS,
+            ));
+        } else if maybe_paddle.is_some() {
+            entity.insert(rapier::RigidBody::KinematicPositionBased);
+        } else {
+            entity.insert(rapier::RigidBody::Fixed);
+        }
+
+        if maybe_loss_zone.is_some() {
+            entity.insert(rapier::Sensor);
+        }
+    }
+
+    for (entity, collider) in &changed_query {
+        commands.entity(entity).insert(shape(collider));
+    }
+}
+
+// Whatever changed the ball's velocity since the last step, e.g. serving it, is passed on to rapier
+fn push_velocities(mut query: Query<(&Velocity, &mut rapier::Velocity)>) {
+    for (velocity, mut body_velocity) in &mut query {
+        body_velocity.linvel = velocity.0;
+    }
+}
+
+fn pull_velocities(mut query: Query<(&mut Velocity, &rapier::Velocity)>) {
+    for (mut velocity, body_velocity) in &mut query {
+        velocity.0 = body_velocity.linvel;
+    }
+}
+
+/// Takes the place of the game's own `check_for_collisions`: rapier already bounced the ball,
+/// so this only reacts to what it ran into, the same way
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn check_for_collisions(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    tunables: Res<Tunables>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut contacts: EventReader<rapier::CollisionEvent>,
+    ball_query: Query<(&Transform, &Collider), With<Ball>>,
+    collider_query: Query<Obstacle, Without<Ball>>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut paddle_hit_events: EventWriter<PaddleHitEvent>,
+    mut brick_destroyed_events: EventWriter<BrickDestroyedEvent>,
+    mut brick_hit_events: EventWriter<BrickHitEvent>,
+    mut ball_lost_events: EventWriter<BallLostEvent>,
+) {
+    for contact in contacts.iter() {
+        // Only the first touch counts, not the ball leaving again
+        let rapier::CollisionEvent::Started(first, second, _) = *contact else {
+            continue;
+        };
+        let (ball, collider_entity) = if ball_query.contains(first) {
+            (first, second)
+        } else if ball_query.contains(second) {
+            (second, first)
+        } else {
+            continue;
+        };
+        let Ok((ball_transform, ball_collider)) = ball_query.get(ball) else {
+            continue;
+        };
+        let Ok((_, transform, collider, maybe_brick, maybe_paddle, maybe_loss_zone)) =
+            collider_query.get(collider_entity)
+        else {
+            continue;
+        };
+
+        // Rapier tells when things start touching, but not where, so work that out the game's way
+        let ball_center = ball_transform.translation.truncate();
+        let point = match *ball_collider {
+            Collider::Circle(radius) => collider
+                .contact(transform, ball_center, radius)
+                .map(|contact| ball_center - contact.normal * (radius - contact.depth)),
+            _ => None,
+        };
+        collision_events.send(CollisionEvent {
+            point: point.unwrap_or(ball_center),
+        });
+
+        if maybe_brick.is_some() {
+            brick_hit_events.send(BrickHitEvent(collider_entity));
+        }
+        match maybe_brick {
+            Some(BrickKind::Normal) => {
+                scoreboard.score += 1;
+                brick_destroyed_events.send(BrickDestroyedEvent(collider_entity));
+                commands.entity(collider_entity).despawn();
+            }
+            Some(BrickKind::Tough) => {
+                let cracked = BrickKind::Normal;
+                let material = brick_assets.material(&mut materials, &tunables, cracked);
+                commands.entity(collider_entity).insert((cracked, material));
+            }
+            Some(BrickKind::Unbreakable) | None => {}
+        }
+
+        if maybe_paddle.is_some() {
+            paddle_hit_events.send_default();
+        }
+
+        if maybe_loss_zone.is_some() {
+            ball_lost_events.send(BallLostEvent {
+                ball,
+                zone: collider_entity,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        headless::Simulation, rng::SimRng, tunables::Tunables, Ball, GameMode, RoundState,
+    };
+    use bevy::prelude::*;
+
+    fn ball_position(simulation: &mut Simulation) -> Vec3 {
+        let world = simulation.world();
+        world
+            .query_filtered::<&Transform, With<Ball>>()
+            .single(world)
+            .translation
+    }
+
+    #[test]
+    fn served_ball_moves() {
+        let mut simulation =
+            Simulation::new(GameMode::Classic, SimRng::new(0), Tunables::default(), None);
+        let start = ball_position(&mut simulation);
+        for _ in 0..10 {
+            simulation.step();
+        }
+        assert_eq!(
+            *simulation.world().resource::<RoundState>(),
+            RoundState::Playing
+        );
+        assert!(ball_position(&mut simulation).distance(start) > 1.0);
+    }
+}
@@ -0,0 +1,183 @@
+//! Levels drawn as pixel art, for the `import-level` binary.
+//!
+//! Every pixel of the image is a cell of the usual grid of bricks, so the image is
+//! as many pixels wide and high as the grid has columns and rows.
+//! A palette file tells which color makes which kind of brick:
+//!
+//! ```ron
+//! (
+//!     bricks: {
+//!         "#8080ff": Normal,
+//!         "#4040bf": Tough,
+//!     },
+//!     background: Some("#000000"),
+//! )
+//! ```
+//!
+//! Transparent pixels and pixels of the background color stay empty.
+//! Any other color missing from the palette is a mistake, and fails the import.
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use bevy::{
+    prelude::*,
+    render::texture::{CompressedImageFormats, ImageType},
+};
+use serde::Deserialize;
+
+use crate::{
+    arena::Arena,
+    level::{BrickKind, Level, LevelBrick, LEVEL_SCHEMA},
+    tunables::Tunables,
+    BrickLayout,
+};
+
+// Pixels less opaque than this are empty
+const MIN_ALPHA: u8 = 128;
+
+#[derive(Deserialize)]
+struct Palette {
+    bricks: BTreeMap<String, BrickKind>,
+    #[serde(default)]
+    background: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Rgb([u8; 3]);
+
+impl Rgb {
+    // `#rrggbb`, with or without the `#`
+    fn parse(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+        Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+/// Turns an image into a level, using the palette to pick the brick kinds.
+/// Fails on the first thing that doesn't fit, saying what and where.
+pub(crate) fn level_from_image(image_path: &Path, palette_path: &Path) -> Result<Level, String> {
+    let palette = load_palette(palette_path)?;
+
+    let bytes = fs::read(image_path).map_err(|err| format!("{}: {err}", image_path.display()))?;
+    let extension = image_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("png");
+    let image = Image::from_buffer(
+        &bytes,
+        ImageType::Extension(extension),
+        CompressedImageFormats::NONE,
+        true,
+    )
+    .map_err(|err| err.to_string())
+    // 8 bit images always come out as RGBA, which this supports
+    .and_then(|image| image.try_into_dynamic().map_err(|err| err.to_string()))
+    .map_err(|err| format!("{}: {err}", image_path.display()))?
+    .to_rgba8();
+
+    let layout = BrickLayout::new(&Tunables::load(), &Arena::default());
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if (width, height) != (layout.n_columns, layout.n_rows) {
+        return Err(format!(
+            "{}: the image is {width}x{height} pixels, but the grid has {}x{} bricks",
+            image_path.display(),
+            layout.n_columns,
+            layout.n_rows
+        ));
+    }
+
+    let mut bricks = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b, a] = image.get_pixel(x as u32, y as u32).0;
+            let color = Rgb([r, g, b]);
+            if a < MIN_ALPHA || Some(color) == palette.background {
+                continue;
+            }
+            let Some(&kind) = palette.bricks.get(&color) else {
+                return Err(format!(
+                    "{}: the pixel at ({x}, {y}) is {color}, which isn't in the palette",
+                    image_path.display()
+                ));
+            };
+
+            // Images go from the top down, the grid from the bottom up
+            let position = layout.position(height - 1 - y, x);
+            bricks.push(LevelBrick {
+                position: (position.x, position.y),
+                kind,
+            });
+        }
+    }
+
+    let name = image_path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    Ok(Level {
+        schema: LEVEL_SCHEMA,
+        name,
+        author: String::new(),
+        arena: None,
+        walls: None,
+        bricks,
+    })
+}
+
+struct ParsedPalette {
+    bricks: BTreeMap<Rgb, BrickKind>,
+    background: Option<Rgb>,
+}
+
+fn load_palette(path: &Path) -> Result<ParsedPalette, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+    let palette: Palette = ron::from_str(&text)
+        .map_err(|err| format!("{}: invalid palette: {err}", path.display()))?;
+
+    let parse = |hex: &str| {
+        Rgb::parse(hex).ok_or_else(|| {
+            format!(
+                "{}: `{hex}` isn't a color, expected #rrggbb",
+                path.display()
+            )
+        })
+    };
+    let mut bricks = BTreeMap::new();
+    for (hex, kind) in &palette.bricks {
+        bricks.insert(parse(hex)?, *kind);
+    }
+    let background = palette.background.as_deref().map(parse).transpose()?;
+    Ok(ParsedPalette { bricks, background })
+}
+
+/// Imports a pixel art level and saves it in the game's format.
+/// The level is named after the image unless given a name, and the number of bricks is returned.
+pub fn import_pixel_art(
+    image: &Path,
+    palette: &Path,
+    output: &Path,
+    name: Option<&str>,
+    author: Option<&str>,
+) -> Result<usize, String> {
+    let mut level = level_from_image(image, palette)?;
+    if let Some(name) = name {
+        level.name = name.to_string();
+    }
+    if let Some(author) = author {
+        level.author = author.to_string();
+    }
+    level
+        .save(output)
+        .map_err(|err| format!("{}: {err}", output.display()))?;
+    Ok(level.bricks.len())
+}
@@ -0,0 +1,310 @@
+//! Remote control of the game over a local TCP socket, for bots and scripted tests.
+//!
+//! Start the game with `--remote <address>`, then connect and exchange JSON messages,
+//! one per line. Every request is answered with `{"state": {...}}`, holding the ball, paddles,
+//! bricks remaining, score, lives and round after the request has been carried out,
+//! or with `{"error": "..."}` if it couldn't be understood.
+//!
+//! - `{"type": "state"}` only asks for the state.
+//! - `{"type": "input", "paddle": 0.5}` steers a paddle, from -1 (full speed left)
+//!   to 1 (full speed right), while the game keeps running in real time.
+//!   An optional `"player": "two"` picks the other paddle in two-player modes.
+//! - `{"type": "step", "paddle": 0.5, "steps": 1}` steers the paddle and then advances the game
+//!   by the given number of fixed steps, at most `MAX_STEPS` at once. From then on the game only
+//!   moves when told to, until the client sends `{"type": "resume"}` or disconnects.
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+};
+
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    run_game_step, set_paddle_input, Ball, Brick, Paddle, Player, PlayerControlled, RoundState,
+    Scoreboard, Velocity,
+};
+
+// Keeps a misbehaving client from eating up all the memory
+const MAX_REQUEST_SIZE: usize = 4096;
+// A minute of play, so a single request can't hang the game for good
+const MAX_STEPS: u32 = 3600;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    State,
+    Input {
+        paddle: f32,
+        #[serde(default = "player_one")]
+        player: Player,
+    },
+    Step {
+        #[serde(default)]
+        paddle: Option<f32>,
+        #[serde(default = "player_one")]
+        player: Player,
+        #[serde(default = "one_step")]
+        steps: u32,
+    },
+    Resume,
+}
+
+fn player_one() -> Player {
+    Player::One
+}
+
+fn one_step() -> u32 {
+    1
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    State(GameState),
+    Error(String),
+}
+
+#[derive(Serialize, Debug)]
+struct GameState {
+    ball: Option<BallState>,
+    paddles: Vec<PaddleState>,
+    bricks_remaining: usize,
+    score: usize,
+    lives: usize,
+    round: RoundState,
+}
+
+#[derive(Serialize, Debug)]
+struct BallState {
+    position: [f32; 2],
+    velocity: [f32; 2],
+}
+
+#[derive(Serialize, Debug)]
+struct PaddleState {
+    player: Player,
+    x: f32,
+}
+
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    // Received bytes that don't make up a whole line yet
+    pending: Vec<u8>,
+}
+
+#[derive(Resource)]
+pub(crate) struct RemoteControl {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    // Whether a client is stepping the game by itself
+    stepping: bool,
+}
+
+impl RemoteControl {
+    pub(crate) fn listen(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!("Listening for remote control on {}", listener.local_addr()?);
+
+        Ok(RemoteControl {
+            listener,
+            clients: Vec::new(),
+            stepping: false,
+        })
+    }
+
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(err) = stream.set_nonblocking(true) {
+                        warn!("Could not set up the remote control connection: {err}");
+                        continue;
+                    }
+                    info!("Remote control client connected from {address}");
+                    self.clients.push(Client {
+                        stream,
+                        address,
+                        pending: Vec::new(),
+                    });
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    warn!("Could not accept a remote control client: {err}");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Client {
+    // Returns the complete lines received so far, or `None` once the client is gone
+    fn receive(&mut self) -> Option<Vec<String>> {
+        let mut buffer = [0; 1024];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return None,
+                Ok(len) => self.pending.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Lost the remote control client {}: {err}", self.address);
+                    return None;
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+        if self.pending.len() > MAX_REQUEST_SIZE {
+            warn!(
+                "Dropping the remote control client {}: request too long",
+                self.address
+            );
+            return None;
+        }
+        Some(lines)
+    }
+
+    fn send(&mut self, response: &Response) -> io::Result<()> {
+        let mut message = serde_json::to_vec(response).map_err(io::Error::from)?;
+        message.push(b'\n');
+        // Responses are small, so waiting for the socket to take them doesn't hold the game up
+        self.stream.set_nonblocking(false)?;
+        let sent = self.stream.write_all(&message);
+        self.stream.set_nonblocking(true)?;
+        sent
+    }
+}
+
+// Pipe the fixed timestep into this, so it leaves the game alone while a client steps it
+pub(crate) fn unless_stepping(
+    In(should_run): In<ShouldRun>,
+    remote: Res<RemoteControl>,
+) -> ShouldRun {
+    if remote.stepping {
+        ShouldRun::No
+    } else {
+        should_run
+    }
+}
+
+// The paddles are steered by the clients rather than the keyboard
+pub(crate) fn take_over_paddles(
+    mut commands: Commands,
+    query: Query<Entity, (With<Paddle>, With<PlayerControlled>)>,
+) {
+    for paddle in &query {
+        commands.entity(paddle).remove::<PlayerControlled>();
+    }
+}
+
+pub(crate) fn serve_clients(world: &mut World) {
+    let mut clients = {
+        let mut remote = world.resource_mut::<RemoteControl>();
+        remote.accept();
+        std::mem::take(&mut remote.clients)
+    };
+
+    clients.retain_mut(|client| {
+        let Some(lines) = client.receive() else {
+            info!("Remote control client {} disconnected", client.address);
+            return false;
+        };
+
+        for line in lines.iter().filter(|line| !line.is_empty()) {
+            let response = match serde_json::from_str(line) {
+                Ok(request) => match handle_request(world, request) {
+                    Ok(()) => Response::State(game_state(world)),
+                    Err(err) => Response::Error(err),
+                },
+                Err(err) => Response::Error(format!("Invalid request: {err}")),
+            };
+            if let Err(err) = client.send(&response) {
+                warn!("Lost the remote control client {}: {err}", client.address);
+                return false;
+            }
+        }
+        true
+    });
+
+    let mut remote = world.resource_mut::<RemoteControl>();
+    // With nobody left to step the game, it goes back to running on its own
+    if clients.is_empty() {
+        remote.stepping = false;
+    }
+    remote.clients = clients;
+}
+
+fn handle_request(world: &mut World, request: Request) -> Result<(), String> {
+    match request {
+        Request::State => {}
+        Request::Input { paddle, player } => set_paddle_input(world, player, paddle),
+        Request::Step {
+            paddle,
+            player,
+            steps,
+        } => {
+            if steps > MAX_STEPS {
+                return Err(format!(
+                    "Too many steps: {steps}, at most {MAX_STEPS} at once"
+                ));
+            }
+            world.resource_mut::<RemoteControl>().stepping = true;
+            if let Some(paddle) = paddle {
+                set_paddle_input(world, player, paddle);
+            }
+            for _ in 0..steps {
+                // Same as the fixed timestep, nothing moves once the round is over
+                if *world.resource::<RoundState>() != RoundState::Playing {
+                    break;
+                }
+                run_game_step(world);
+            }
+        }
+        Request::Resume => world.resource_mut::<RemoteControl>().stepping = false,
+    }
+    Ok(())
+}
+
+fn game_state(world: &mut World) -> GameState {
+    let ball = world
+        .query_filtered::<(&Transform, &Velocity), With<Ball>>()
+        .iter(world)
+        .next()
+        .map(|(transform, velocity)| BallState {
+            position: transform.translation.truncate().to_array(),
+            velocity: velocity.to_array(),
+        });
+
+    let mut paddles: Vec<PaddleState> = world
+        .query_filtered::<(&Transform, &Player), With<Paddle>>()
+        .iter(world)
+        .map(|(transform, player)| PaddleState {
+            player: *player,
+            x: transform.translation.x,
+        })
+        .collect();
+    paddles.sort_by_key(|paddle| paddle.player.index());
+
+    let bricks_remaining = world
+        .query_filtered::<(), With<Brick>>()
+        .iter(world)
+        .count();
+    let scoreboard = world.resource::<Scoreboard>();
+
+    GameState {
+        ball,
+        paddles,
+        bricks_remaining,
+        score: scoreboard.score,
+        lives: scoreboard.lives,
+        round: *world.resource::<RoundState>(),
+    }
+}
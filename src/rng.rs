@@ -0,0 +1,46 @@
+//! A small random number generator for gameplay decisions.
+//! It is seeded explicitly, so the same seed always plays out the same way.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+#[derive(Resource, Clone, Debug)]
+pub(crate) struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        SimRng { state: seed }
+    }
+
+    /// Seeds the generator from the clock, for when reproducibility doesn't matter
+    pub(crate) fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default();
+        SimRng::new(nanos)
+    }
+
+    // SplitMix64, see https://prng.di.unimi.it/splitmix64.c
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `[0, 1)`
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fit exactly in an f32 mantissa
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed in `[min, max)`
+    pub(crate) fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
@@ -0,0 +1,82 @@
+//! Rollback for networked games.
+//!
+//! Every step starts by taking a snapshot of the game. When the other player's actual input
+//! for a step turns out to differ from the guess it was simulated with, the game is restored
+//! to the snapshot of that step and every step since is simulated again with the right inputs.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::{lockstep::Lockstep, snapshot::GameSnapshot};
+
+#[derive(Resource, Default)]
+pub(crate) struct Rollback {
+    // The state at the start of every step that may still have to be simulated again
+    snapshots: BTreeMap<u32, GameSnapshot>,
+    prediction_misses: usize,
+}
+
+// Used in place of `crate::run_game_step` when rolling back
+pub(crate) fn run_game_step(world: &mut World) {
+    let misprediction = world.resource_mut::<Lockstep>().take_misprediction();
+    if let Some(tick) = misprediction {
+        roll_back(world, tick);
+    }
+
+    save_and_step(world);
+    confirm_steps(world);
+}
+
+fn save_and_step(world: &mut World) {
+    let tick = world.resource::<Lockstep>().tick();
+    let snapshot = GameSnapshot::capture(world);
+    world
+        .resource_mut::<Rollback>()
+        .snapshots
+        .insert(tick, snapshot);
+    crate::run_game_step(world);
+}
+
+// Simulate everything from the given step onwards again
+fn roll_back(world: &mut World, from: u32) {
+    let present = world.resource::<Lockstep>().tick();
+    let Some(snapshot) = world.resource::<Rollback>().snapshots.get(&from).cloned() else {
+        warn!("Can't roll back to step {from}, its snapshot is gone");
+        return;
+    };
+
+    snapshot.restore(world);
+    world.resource_mut::<Lockstep>().rewind(from);
+    for _ in from..present {
+        save_and_step(world);
+    }
+
+    let mut rollback = world.resource_mut::<Rollback>();
+    rollback.prediction_misses += 1;
+    info!(
+        "Mispredicted the other player's input at step {from}, simulated {} steps again \
+         ({} misses so far)",
+        present - from,
+        rollback.prediction_misses
+    );
+}
+
+// Once both players' inputs are known up to some step, the state at its start is final:
+// it can be compared with the other peer, and earlier snapshots are no longer needed
+fn confirm_steps(world: &mut World) {
+    let confirmed = world.resource::<Lockstep>().confirmed_steps();
+
+    let mut rollback = world.resource_mut::<Rollback>();
+    let checksums: Vec<(u32, u64)> = rollback
+        .snapshots
+        .range(..confirmed)
+        .map(|(&tick, snapshot)| (tick, snapshot.checksum()))
+        .collect();
+    rollback.snapshots = rollback.snapshots.split_off(&confirmed);
+
+    let mut lockstep = world.resource_mut::<Lockstep>();
+    for (tick, checksum) in checksums {
+        lockstep.record_confirmed_checksum(tick, checksum);
+    }
+}
@@ -0,0 +1,278 @@
+//! Snapshots of the whole game state, which can be restored later on.
+//! They let a networked game go back in time and redo some steps,
+//! and double as quick save states: F5 saves the game and F9 loads it again.
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    autopilot::Autopilot, endless::EndlessState, level::BrickKind, lockstep::Checksum, rng::SimRng,
+    time_attack::TimeAttackRules, time_attack::TimeBonus, versus::VersusScores, Ball, Brick,
+    BrickId, Collider, NextBrickId, Paddle, Player, RoundState, Scoreboard, Velocity,
+};
+
+/// Everything a step of the game depends on
+#[derive(Clone)]
+pub(crate) struct GameSnapshot {
+    balls: Vec<(Entity, Transform, Vec2)>,
+    paddles: Vec<PaddleSnapshot>,
+    bricks: Vec<BrickSnapshot>,
+    next_brick_id: NextBrickId,
+    scoreboard: Scoreboard,
+    round: RoundState,
+    rng: SimRng,
+    // Only around in the game modes using them
+    versus_scores: Option<VersusScores>,
+    endless: Option<EndlessState>,
+    time_attack: Option<TimeAttackRules>,
+}
+
+#[derive(Clone)]
+struct PaddleSnapshot {
+    entity: Entity,
+    player: Player,
+    transform: Transform,
+    // Where it is headed and when it looks at the ball next, for computer controlled paddles
+    autopilot: Option<Autopilot>,
+}
+
+// Enough to spawn a brick again if it has been destroyed since
+#[derive(Clone)]
+struct BrickSnapshot {
+    id: BrickId,
+    transform: Transform,
+    kind: BrickKind,
+    collider: Collider,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    time_bonus: bool,
+    owner: Option<Player>,
+}
+
+impl GameSnapshot {
+    pub(crate) fn capture(world: &mut World) -> Self {
+        let balls = world
+            .query_filtered::<(Entity, &Transform, &Velocity), With<Ball>>()
+            .iter(world)
+            .map(|(entity, transform, velocity)| (entity, *transform, velocity.0))
+            .collect();
+
+        let mut paddles: Vec<PaddleSnapshot> = world
+            .query_filtered::<(Entity, &Player, &Transform, Option<&Autopilot>), With<Paddle>>()
+            .iter(world)
+            .map(|(entity, player, transform, autopilot)| PaddleSnapshot {
+                entity,
+                player: *player,
+                transform: *transform,
+                autopilot: autopilot.cloned(),
+            })
+            .collect();
+        paddles.sort_by_key(|paddle| paddle.player.index());
+
+        let bricks = world
+            .query_filtered::<(
+                &BrickId,
+                &Transform,
+                &BrickKind,
+                &Collider,
+                &Handle<Mesh>,
+                &Handle<StandardMaterial>,
+                Option<&TimeBonus>,
+                Option<&Player>,
+            ), With<Brick>>()
+            .iter(world)
+            .map(
+                |(id, transform, kind, collider, mesh, material, time_bonus, owner)| {
+                    BrickSnapshot {
+                        id: *id,
+                        transform: *transform,
+                        kind: *kind,
+                        collider: *collider,
+                        mesh: mesh.clone(),
+                        material: material.clone(),
+                        time_bonus: time_bonus.is_some(),
+                        owner: owner.copied(),
+                    }
+                },
+            )
+            .collect();
+
+        GameSnapshot {
+            balls,
+            paddles,
+            bricks,
+            next_brick_id: *world.resource::<NextBrickId>(),
+            scoreboard: world.resource::<Scoreboard>().clone(),
+            round: *world.resource::<RoundState>(),
+            rng: world.resource::<SimRng>().clone(),
+            versus_scores: world.get_resource::<VersusScores>().cloned(),
+            endless: world.get_resource::<EndlessState>().cloned(),
+            time_attack: world.get_resource::<TimeAttackRules>().cloned(),
+        }
+    }
+
+    pub(crate) fn restore(&self, world: &mut World) {
+        for (entity, transform, velocity) in &self.balls {
+            if let Some(mut ball) = world.get_entity_mut(*entity) {
+                ball.insert((*transform, Velocity(*velocity)));
+            }
+        }
+        for snapshot in &self.paddles {
+            if let Some(mut paddle) = world.get_entity_mut(snapshot.entity) {
+                paddle.insert(snapshot.transform);
+                if let Some(autopilot) = &snapshot.autopilot {
+                    paddle.insert(autopilot.clone());
+                }
+            }
+        }
+
+        // Bricks that didn't exist yet are removed, and destroyed ones are spawned again.
+        // They are told apart by id, as a brick spawned again is another entity.
+        let mut current_bricks: HashMap<BrickId, Entity> = world
+            .query_filtered::<(Entity, &BrickId), With<Brick>>()
+            .iter(world)
+            .map(|(entity, id)| (*id, entity))
+            .collect();
+        current_bricks.retain(|id, brick| {
+            let kept = self.bricks.iter().any(|snapshot| snapshot.id == *id);
+            if !kept {
+                world.despawn(*brick);
+            }
+            kept
+        });
+        for snapshot in &self.bricks {
+            match current_bricks.get(&snapshot.id) {
+                // Tough bricks may have cracked since
+                Some(&brick) => {
+                    world.entity_mut(brick).insert((
+                        snapshot.transform,
+                        snapshot.kind,
+                        snapshot.material.clone(),
+                    ));
+                }
+                None => {
+                    let mut brick = world.spawn((
+                        PbrBundle {
+                            mesh: snapshot.mesh.clone(),
+                            material: snapshot.material.clone(),
+                            transform: snapshot.transform,
+                            ..default()
+                        },
+                        Brick,
+                        snapshot.id,
+                        snapshot.kind,
+                        snapshot.collider,
+                    ));
+                    if snapshot.time_bonus {
+                        brick.insert(TimeBonus);
+                    }
+                    if let Some(owner) = snapshot.owner {
+                        brick.insert(owner);
+                    }
+                }
+            }
+        }
+
+        world.insert_resource(self.next_brick_id);
+        world.insert_resource(self.scoreboard.clone());
+        world.insert_resource(self.round);
+        world.insert_resource(self.rng.clone());
+        if let Some(versus_scores) = &self.versus_scores {
+            world.insert_resource(versus_scores.clone());
+        }
+        if let Some(endless) = &self.endless {
+            world.insert_resource(endless.clone());
+        }
+        if let Some(time_attack) = &self.time_attack {
+            world.insert_resource(time_attack.clone());
+        }
+    }
+
+    /// Hash of the state, for comparing it with another peer's
+    pub(crate) fn checksum(&self) -> u64 {
+        let mut checksum = Checksum::default();
+        for (_, transform, velocity) in &self.balls {
+            checksum.write_vec2(transform.translation.truncate());
+            checksum.write_vec2(*velocity);
+        }
+        for paddle in &self.paddles {
+            checksum.write_f32(paddle.transform.translation.x);
+        }
+        checksum.write_u64(self.bricks.len() as u64);
+        let tough = self.bricks.iter().filter(|brick| brick.kind == BrickKind::Tough);
+        checksum.write_u64(tough.count() as u64);
+        checksum.write_u64(self.scoreboard.score as u64);
+        checksum.write_u64(self.scoreboard.lives as u64);
+        if let Some(scores) = &self.versus_scores {
+            checksum.write_u64(scores.points(Player::One) as u64);
+            checksum.write_u64(scores.points(Player::Two) as u64);
+        }
+        checksum.finish()
+    }
+}
+
+/// The quick save slot
+#[derive(Resource, Default)]
+pub(crate) struct SaveState(Option<GameSnapshot>);
+
+pub(crate) fn quick_save_and_load(world: &mut World) {
+    let keyboard_input = world.resource::<Input<KeyCode>>();
+    let save = keyboard_input.just_pressed(KeyCode::F5);
+    let load = keyboard_input.just_pressed(KeyCode::F9);
+
+    if save {
+        let snapshot = GameSnapshot::capture(world);
+        world.resource_mut::<SaveState>().0 = Some(snapshot);
+        info!("Game saved");
+    } else if load {
+        // There is no coming back from the game over screen
+        if *world.resource::<RoundState>() != RoundState::Playing {
+            return;
+        }
+        let Some(snapshot) = world.resource::<SaveState>().0.clone() else {
+            return;
+        };
+        snapshot.restore(world);
+        info!("Game loaded");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless::Simulation, tunables::Tunables, GameMode};
+
+    fn brick_ids(world: &mut World) -> Vec<BrickId> {
+        let mut ids: Vec<BrickId> = world.query::<&BrickId>().iter(world).copied().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn replays_the_same_steps_after_a_restore() {
+        let mut simulation =
+            Simulation::new(GameMode::Classic, SimRng::new(0), Tunables::default(), None);
+        simulation.hand_over_to_autopilot();
+        // Partway through the autopilot's reaction time, with a target in mind
+        for _ in 0..4 {
+            simulation.step();
+        }
+        let snapshot = GameSnapshot::capture(simulation.world());
+        let bricks = brick_ids(simulation.world());
+
+        // Play until a brick breaks, which the restore has to spawn again
+        let mut steps = 0;
+        while brick_ids(simulation.world()).len() == bricks.len() {
+            assert!(steps < 10_000, "No brick broke in {steps} steps");
+            simulation.step();
+            steps += 1;
+        }
+        let played = GameSnapshot::capture(simulation.world()).checksum();
+
+        snapshot.restore(simulation.world());
+        assert_eq!(brick_ids(simulation.world()), bricks);
+        for _ in 0..steps {
+            simulation.step();
+        }
+        assert_eq!(GameSnapshot::capture(simulation.world()).checksum(), played);
+    }
+}
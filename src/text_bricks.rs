@@ -0,0 +1,228 @@
+//! Brick layouts spelling out text, for event levels: `--text "message" [--font path]`,
+//! or typed into the editor.
+//!
+//! The text is drawn with one of the bundled fonts, scaled to fill the part of the arena
+//! the usual grid of bricks is in, and a brick goes into every cell that is mostly covered by a glyph.
+//! The cells are the ones of that grid, which the editor snaps bricks to as well,
+//! so only a handful of letters fit on a line. Short words read best,
+//! and a new line can be started with `\n`.
+
+use std::{fs, path::Path};
+
+use ab_glyph::{point, Font as _, FontVec, PxScale, ScaleFont as _};
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena,
+    level::{BrickKind, Level, LevelBrick, LEVEL_SCHEMA},
+    tunables::Tunables,
+    BrickLayout,
+};
+
+pub(crate) const DEFAULT_FONT: &str = "assets/fonts/FiraSans-Bold.ttf";
+
+// Glyphs are drawn at this size before being scaled to the arena, in pixels
+const GLYPH_SIZE: f32 = 64.0;
+// How many points of each cell are checked for whether they are inside a glyph, per side
+const SAMPLES_PER_CELL: usize = 4;
+// Share of a cell's points that have to be covered for it to get a brick
+const MIN_COVERAGE: f32 = 0.5;
+
+// The coverage of every pixel of some text, line by line
+struct TextImage {
+    width: usize,
+    height: usize,
+    coverage: Vec<f32>,
+}
+
+impl TextImage {
+    fn draw(text: &str, font: &FontVec) -> Self {
+        let font = font.as_scaled(PxScale::from(GLYPH_SIZE));
+        let line_height = font.height() + font.line_gap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        let line_width = |line: &str| {
+            let mut width: f32 = 0.0;
+            let mut previous = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    width += font.kern(previous, id);
+                }
+                width += font.h_advance(id);
+                previous = Some(id);
+            }
+            width
+        };
+        let width = lines
+            .iter()
+            .map(|line| line_width(line))
+            .fold(0.0, f32::max)
+            .ceil() as usize;
+        let height = (lines.len() as f32 * line_height).ceil() as usize;
+
+        let mut image = TextImage {
+            width,
+            height,
+            coverage: vec![0.0; width * height],
+        };
+        for (index, line) in lines.iter().enumerate() {
+            // Every line is centered
+            let mut caret = (width as f32 - line_width(line)) / 2.0;
+            let baseline = index as f32 * line_height + font.ascent();
+            let mut previous = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    caret += font.kern(previous, id);
+                }
+                let glyph = id.with_scale_and_position(GLYPH_SIZE, point(caret, baseline));
+                caret += font.h_advance(id);
+                previous = Some(id);
+
+                let Some(outline) = font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outline.px_bounds();
+                outline.draw(|x, y, coverage| {
+                    let x = bounds.min.x as i64 + x as i64;
+                    let y = bounds.min.y as i64 + y as i64;
+                    if let Some(pixel) = image.pixel_mut(x, y) {
+                        *pixel = pixel.max(coverage);
+                    }
+                });
+            }
+        }
+        image
+    }
+
+    fn pixel_mut(&mut self, x: i64, y: i64) -> Option<&mut f32> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        self.coverage.get_mut(y as usize * self.width + x as usize)
+    }
+
+    fn pixel(&self, x: f32, y: f32) -> f32 {
+        if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+            return 0.0;
+        }
+        self.coverage[y as usize * self.width + x as usize]
+    }
+}
+
+/// A level of normal bricks spelling out the text in the font at the given path.
+/// They take the place of the bricks of the given level, whose arena and walls are kept.
+pub(crate) fn text_level(
+    text: &str,
+    font_path: &Path,
+    tunables: &Tunables,
+    level: Option<&Level>,
+) -> Result<Level, String> {
+    let arena = level.map(Level::arena).unwrap_or_default();
+    let bricks = text_bricks(text, font_path, tunables, &arena)?;
+    Ok(Level {
+        schema: LEVEL_SCHEMA,
+        name: text.replace("\\n", "\n").trim().replace('\n', " "),
+        author: String::new(),
+        arena: level.and_then(|level| level.arena),
+        walls: level.and_then(|level| level.walls.clone()),
+        bricks,
+    })
+}
+
+/// Normal bricks spelling out the text in the font at the given path,
+/// on the grid of bricks of the given arena
+pub(crate) fn text_bricks(
+    text: &str,
+    font_path: &Path,
+    tunables: &Tunables,
+    arena: &Arena,
+) -> Result<Vec<LevelBrick>, String> {
+    let bytes = fs::read(font_path).map_err(|err| format!("{}: {err}", font_path.display()))?;
+    let font =
+        FontVec::try_from_vec(bytes).map_err(|err| format!("{}: {err}", font_path.display()))?;
+    // `\n` starts a new line, so the text can be given on the command line
+    let text = text.replace("\\n", "\n").trim().to_string();
+    let image = TextImage::draw(&text, &font);
+    if image.width == 0 || image.height == 0 {
+        return Err("there is no text to draw".to_string());
+    }
+
+    // The usual grid of bricks, clear of where the ball is served.
+    // Each cell reaches halfway to its neighbours.
+    let layout = BrickLayout::new(tunables, arena);
+    let (rows, columns) = (layout.n_rows, layout.n_columns);
+    let grid_size = Vec2::new(columns as f32, rows as f32) * layout.step;
+    let grid_min = layout.position(0, 0) - layout.step / 2.0;
+
+    // The text is as big as it can be while keeping its proportions,
+    // and centered in the grid
+    let image_size = Vec2::new(image.width as f32, image.height as f32);
+    let scale = (grid_size / image_size).min_element();
+    let text_min = grid_min + (grid_size - image_size * scale) / 2.0;
+    let text_top = text_min.y + image_size.y * scale;
+
+    let mut bricks = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let center = layout.position(row, column);
+            let cell_min = center - layout.step / 2.0;
+            let mut covered = 0.0;
+            for sample_y in 0..SAMPLES_PER_CELL {
+                for sample_x in 0..SAMPLES_PER_CELL {
+                    let offset = (Vec2::new(sample_x as f32, sample_y as f32) + 0.5)
+                        / SAMPLES_PER_CELL as f32;
+                    let point = cell_min + offset * layout.step;
+                    // Images go from the top down
+                    let x = (point.x - text_min.x) / scale;
+                    let y = (text_top - point.y) / scale;
+                    covered += image.pixel(x, y);
+                }
+            }
+
+            if covered / (SAMPLES_PER_CELL * SAMPLES_PER_CELL) as f32 >= MIN_COVERAGE {
+                bricks.push(LevelBrick {
+                    position: (center.x, center.y),
+                    kind: BrickKind::Normal,
+                });
+            }
+        }
+    }
+
+    if bricks.is_empty() {
+        return Err(format!("`{text}` is too thin to make out of bricks"));
+    }
+    Ok(bricks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_bricks_up_with_the_grid() {
+        let tunables = Tunables::default();
+        let arena = Arena::default();
+        let layout = BrickLayout::new(&tunables, &arena);
+        let bricks = text_bricks("HI", Path::new(DEFAULT_FONT), &tunables, &arena).unwrap();
+        for brick in &bricks {
+            let cell = (Vec2::from(brick.position) - layout.origin) / layout.step;
+            assert!(
+                (cell - cell.round()).abs().max_element() < 1e-3,
+                "{:?} is off the grid",
+                brick.position
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_walls_of_the_level_it_replaces() {
+        let tunables = Tunables::default();
+        let mut level = Level::grid(&tunables);
+        level.walls = Some(vec![(-400.0, -300.0), (0.0, 300.0), (400.0, -300.0)]);
+        let text_level =
+            text_level("HI", Path::new(DEFAULT_FONT), &tunables, Some(&level)).unwrap();
+        assert_eq!(text_level.walls, level.walls);
+    }
+}
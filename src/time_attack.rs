@@ -0,0 +1,124 @@
+//! Time-attack mode: clear the level before the countdown runs out.
+//! Some bricks give extra time, and the seconds left over add to the score.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    tunables::Tunables, Brick, BrickDestroyedEvent, RoundState, Scoreboard, SCOREBOARD_FONT_SIZE,
+    SCOREBOARD_TEXT_PADDING, TIME_STEP,
+};
+
+/// The rules of a time-attack round, along with the time left in it
+#[derive(Resource, Clone)]
+pub(crate) struct TimeAttackRules {
+    countdown: Timer,
+    // Seconds added to the countdown by breaking a bonus brick
+    bonus_seconds: f32,
+    // One out of this many bricks is a bonus brick
+    bonus_brick_every: usize,
+    // Points awarded for every whole second left on the clock
+    points_per_second_left: usize,
+}
+
+impl Default for TimeAttackRules {
+    fn default() -> Self {
+        TimeAttackRules {
+            countdown: Timer::from_seconds(90.0, TimerMode::Once),
+            bonus_seconds: 5.0,
+            bonus_brick_every: 4,
+            points_per_second_left: 10,
+        }
+    }
+}
+
+// Breaking a brick with this component adds time to the countdown
+#[derive(Component)]
+pub(crate) struct TimeBonus;
+
+#[derive(Component)]
+pub(crate) struct CountdownText;
+
+pub(crate) fn setup_time_attack(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    rules: Res<TimeAttackRules>,
+    asset_server: Res<AssetServer>,
+    tunables: Res<Tunables>,
+    mut brick_query: Query<(Entity, &mut Handle<StandardMaterial>), With<Brick>>,
+) {
+    let bonus_material = materials.add(tunables.colors.time_bonus_brick.into());
+    for (entity, mut material) in brick_query.iter_mut().step_by(rules.bonus_brick_every) {
+        *material = bonus_material.clone();
+        commands.entity(entity).insert(TimeBonus);
+    }
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "Time: ",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    color: tunables.colors.text,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: SCOREBOARD_TEXT_PADDING,
+                right: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+            ..default()
+        }),
+        CountdownText,
+    ));
+}
+
+pub(crate) fn count_down(
+    mut rules: ResMut<TimeAttackRules>,
+    mut round: ResMut<RoundState>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut brick_destroyed_events: EventReader<BrickDestroyedEvent>,
+    bonus_query: Query<(), With<TimeBonus>>,
+) {
+    // `check_level_cleared` runs first, so a cleared level cashes in the time left
+    if *round == RoundState::Cleared {
+        let seconds_left = rules.countdown.remaining().as_secs() as usize;
+        scoreboard.score += seconds_left * rules.points_per_second_left;
+        return;
+    }
+
+    // The destroyed bricks are only despawned at the end of the step,
+    // so we can still check which of them were bonus bricks
+    let bonuses = brick_destroyed_events
+        .iter()
+        .filter(|BrickDestroyedEvent(entity)| bonus_query.contains(*entity))
+        .count();
+    if bonuses > 0 {
+        let extra = Duration::from_secs_f32(bonuses as f32 * rules.bonus_seconds);
+        let duration = rules.countdown.duration() + extra;
+        rules.countdown.set_duration(duration);
+    }
+
+    rules.countdown.tick(Duration::from_secs_f32(TIME_STEP));
+    if rules.countdown.finished() {
+        *round = RoundState::GameOver;
+    }
+}
+
+pub(crate) fn update_countdown_text(
+    rules: Res<TimeAttackRules>,
+    mut query: Query<&mut Text, With<CountdownText>>,
+) {
+    let mut text = query.single_mut();
+    text.sections[1].value = format!("{:.1}", rules.countdown.remaining_secs());
+}
@@ -0,0 +1,196 @@
+//! A preview of where the ball is going, drawn as a line through its next few bounces.
+//!
+//! It is worked out by moving a copy of the ball step by step, bouncing it off the walls,
+//! paddles and bricks the same way the game does, without touching the game itself.
+//! Bricks the ball would break are left out once it bounced off them, and the line ends
+//! where the ball would be lost.
+//!
+//! With `--practice` the line is always shown. Otherwise it is shown at the start of the game and
+//! after each lost ball, when the ball is served again, until the ball hits something.
+//!
+//! The line is worked out again only when the ball changes direction. In between, it keeps
+//! starting from where the ball last bounced, which is the line the ball is travelling along.
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    broadphase::{self, Broadphase},
+    collision::Collider,
+    tunables::Tunables,
+    wall::{LineMaterial, LineStrip},
+    Ball, BallLostEvent, BrickKind, CollisionEvent, Obstacle, Velocity, BALL_RADIUS, TIME_STEP,
+};
+
+// How many bounces ahead the line goes
+const PREVIEW_BOUNCES: usize = 3;
+// Gives up on balls that take too long to bounce, e.g. going straight up and down a long way
+const MAX_PREVIEW_STEPS: usize = 600;
+const PREVIEW_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+// In front of the bricks and paddles, so they don't hide the line
+const PREVIEW_Z: f32 = BALL_RADIUS;
+
+#[derive(Resource)]
+pub(crate) struct TrajectoryPreview {
+    // Shown all the time, not just after a serve
+    practice: bool,
+    // The game just started or the ball was just lost, and the ball hasn't hit anything since
+    serving: bool,
+    // The ball's velocity when the line on screen was worked out, if there is one
+    drawn_for: Option<Vec2>,
+}
+
+impl TrajectoryPreview {
+    pub(crate) fn new(practice: bool) -> Self {
+        TrajectoryPreview {
+            practice,
+            // The first ball is served right away
+            serving: true,
+            drawn_for: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub(crate) struct TrajectoryLine;
+
+pub(crate) fn setup_trajectory_line(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+) {
+    commands.spawn((
+        MaterialMeshBundle {
+            // Hidden until there is a path to draw
+            mesh: meshes.add(Mesh::from(LineStrip {
+                points: vec![Vec3::ZERO, Vec3::ZERO],
+            })),
+            material: materials.add(LineMaterial {
+                color: PREVIEW_COLOR,
+            }),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        },
+        TrajectoryLine,
+    ));
+}
+
+// The ball is served again once it is lost, and is on its way once it hits anything.
+// Only modes that lose the ball serve it more than once.
+pub(crate) fn track_serves(
+    mut preview: ResMut<TrajectoryPreview>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut ball_lost_events: EventReader<BallLostEvent>,
+) {
+    // Losing the ball comes with a collision with the loss zone, so check for losses last
+    if collision_events.iter().count() > 0 {
+        preview.serving = false;
+    }
+    if ball_lost_events.iter().count() > 0 {
+        preview.serving = true;
+        // Served the same way it was the last time, but from somewhere else
+        preview.drawn_for = None;
+    }
+}
+
+// Runs while things are still where the steps left them,
+// before they are moved to where they are drawn
+pub(crate) fn draw_trajectory(
+    mut preview: ResMut<TrajectoryPreview>,
+    tunables: Res<Tunables>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut line_query: Query<(&Handle<Mesh>, &mut Visibility), With<TrajectoryLine>>,
+    ball_query: Query<(&Transform, &Velocity, &Collider), With<Ball>>,
+    collider_query: Query<Obstacle, Without<Ball>>,
+) {
+    let Ok((mesh, mut visibility)) = line_query.get_single_mut() else {
+        return;
+    };
+    let path = match ball_query.get_single() {
+        Ok((transform, velocity, &Collider::Circle(radius)))
+            if preview.practice || preview.serving =>
+        {
+            // Still on its way along the line on screen
+            if preview.drawn_for == Some(velocity.0) {
+                return;
+            }
+            preview.drawn_for = Some(velocity.0);
+            predict_path(
+                transform.translation.truncate(),
+                velocity.0,
+                radius,
+                &tunables,
+                &collider_query,
+            )
+        }
+        _ => {
+            preview.drawn_for = None;
+            Vec::new()
+        }
+    };
+
+    visibility.is_visible = path.len() >= 2;
+    if !visibility.is_visible {
+        return;
+    }
+    if let Some(mesh) = meshes.get_mut(mesh) {
+        *mesh = Mesh::from(LineStrip {
+            points: path
+                .into_iter()
+                .map(|point| point.extend(PREVIEW_Z))
+                .collect(),
+        });
+    }
+}
+
+// Where the ball starts out, bounces, and either ends up after the last bounce or is lost
+fn predict_path(
+    mut center: Vec2,
+    mut velocity: Vec2,
+    radius: f32,
+    tunables: &Tunables,
+    collider_query: &Query<Obstacle, Without<Ball>>,
+) -> Vec<Vec2> {
+    // A grid of its own, as the game's may not be kept up to date, e.g. with rapier
+    let mut grid = Broadphase::new(broadphase::cell_size(tunables));
+    for (entity, transform, collider, _, _, _) in collider_query {
+        grid.insert(entity, transform, collider);
+    }
+    let mut broken = HashSet::new();
+
+    let mut path = vec![center];
+    for _ in 0..MAX_PREVIEW_STEPS {
+        center += velocity * TIME_STEP;
+
+        let mut bounced = false;
+        let nearby = grid.near(center, radius);
+        for (entity, transform, collider, maybe_brick, _, maybe_loss_zone) in
+            collider_query.iter_many(&nearby)
+        {
+            if broken.contains(&entity) {
+                continue;
+            }
+            let Some(contact) = collider.contact(transform, center, radius) else {
+                continue;
+            };
+            if maybe_loss_zone.is_some() {
+                path.push(center);
+                return path;
+            }
+            if maybe_brick == Some(&BrickKind::Normal) {
+                broken.insert(entity);
+            }
+            contact.bounce(&mut center, &mut velocity);
+            bounced = true;
+        }
+
+        if bounced {
+            path.push(center);
+            if path.len() > PREVIEW_BOUNCES {
+                return path;
+            }
+        }
+    }
+
+    path.push(center);
+    path
+}
@@ -0,0 +1,439 @@
+//! Gameplay numbers and colors, read from `assets/gameplay.tunables.ron`.
+//!
+//! The file is watched while the game runs: saving it updates the speeds, sizes and colors
+//! on the spot, without restarting the game. The gaps between bricks only shape the layout,
+//! so they take effect the next time bricks are laid out, as do the colors of the bricks
+//! versus and time-attack modes hand out.
+//! Anything missing from the file keeps its default value. A file the game can't be played with,
+//! e.g. with bricks too big to fit, is ignored.
+//!
+//! Both players of a network game need the same file, or their games drift apart.
+
+use std::fs;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{Arena, MIN_ARENA_SIZE},
+    wall::LineMaterial,
+    Ball, Brick, BrickAssets, Collider, Paddle, Player, Velocity, Wall, INITIAL_BALL_DIRECTION,
+};
+
+/// Where the tunables are read from, in the assets directory
+const TUNABLES_ASSET: &str = "gameplay.tunables.ron";
+
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6f1f4c8e-4a0e-4d5b-9d2c-3b8f5e7a1c24"]
+#[serde(default)]
+pub(crate) struct Tunables {
+    // Sizes and speeds are in `Transform` units, and per second
+    pub(crate) paddle_speed: f32,
+    pub(crate) paddle_size: Vec3,
+    pub(crate) ball_speed: f32,
+    pub(crate) brick_size: Vec2,
+    // These values are exact
+    pub(crate) gap_between_paddle_and_bricks: f32,
+    pub(crate) gap_between_bricks: f32,
+    // These values are lower bounds, as the number of bricks is computed
+    pub(crate) gap_between_bricks_and_ceiling: f32,
+    pub(crate) gap_between_bricks_and_sides: f32,
+    // Used instead of `gap_between_paddle_and_bricks` when there are paddles at the top and bottom
+    pub(crate) gap_between_paddle_and_bricks_two_sided: f32,
+    pub(crate) colors: TunedColors,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct TunedColors {
+    pub(crate) background: Color,
+    pub(crate) player_one_paddle: Color,
+    pub(crate) player_two_paddle: Color,
+    pub(crate) ball: Color,
+    pub(crate) brick: Color,
+    pub(crate) tough_brick: Color,
+    pub(crate) unbreakable_brick: Color,
+    // Bricks adding time to the countdown, in time-attack mode
+    pub(crate) time_bonus_brick: Color,
+    // The wall of bricks each player defends, in versus mode
+    pub(crate) player_one_brick: Color,
+    pub(crate) player_two_brick: Color,
+    pub(crate) wall: Color,
+    pub(crate) text: Color,
+    pub(crate) score: Color,
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Tunables {
+            paddle_speed: 500.0,
+            paddle_size: Vec3::new(120.0, 20.0, 8.0),
+            ball_speed: 200.0,
+            brick_size: Vec2::new(80., 15.),
+            gap_between_paddle_and_bricks: 270.0,
+            gap_between_bricks: 40.0,
+            gap_between_bricks_and_ceiling: 20.0,
+            gap_between_bricks_and_sides: 20.0,
+            gap_between_paddle_and_bricks_two_sided: 130.0,
+            colors: TunedColors::default(),
+        }
+    }
+}
+
+impl Default for TunedColors {
+    fn default() -> Self {
+        TunedColors {
+            background: Color::rgb(0.9, 0.9, 0.9),
+            player_one_paddle: Color::rgb(0.8, 0.23, 0.23),
+            player_two_paddle: Color::rgb(0.3, 0.3, 0.7),
+            ball: Color::rgb(0.8, 0.7, 0.6),
+            brick: Color::rgb(0.5, 0.5, 1.0),
+            tough_brick: Color::rgb(0.25, 0.25, 0.75),
+            unbreakable_brick: Color::rgb(0.45, 0.45, 0.45),
+            time_bonus_brick: Color::rgb(1.0, 0.8, 0.3),
+            player_one_brick: Color::rgb(1.0, 0.6, 0.6),
+            player_two_brick: Color::rgb(0.5, 0.5, 1.0),
+            wall: Color::GREEN,
+            text: Color::rgb(0.5, 0.5, 1.0),
+            score: Color::rgb(1.0, 0.5, 0.5),
+        }
+    }
+}
+
+impl Tunables {
+    /// Reads the tunables file right away, for the first frame and for the tools
+    /// running without an asset server. Falls back to the defaults if there is no usable file.
+    pub(crate) fn load() -> Self {
+        let path = format!("assets/{TUNABLES_ASSET}");
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Tunables::default();
+        };
+        let tunables: Tunables = match ron::from_str(&text) {
+            Ok(tunables) => tunables,
+            Err(err) => {
+                warn!("Ignoring {path}: {err}");
+                return Tunables::default();
+            }
+        };
+        if let Err(err) = tunables.validate() {
+            warn!("Ignoring {path}: {err}");
+            return Tunables::default();
+        }
+        tunables
+    }
+
+    /// Whether the game can be played with these tunables, in any arena.
+    /// The bricks are laid out in the smallest arena there can be, as bigger ones only have more room.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("ball_speed", self.ball_speed),
+            ("paddle_size.x", self.paddle_size.x),
+            ("paddle_size.y", self.paddle_size.y),
+            ("paddle_size.z", self.paddle_size.z),
+            ("brick_size.x", self.brick_size.x),
+            ("brick_size.y", self.brick_size.y),
+        ];
+        for (name, value) in positive {
+            if value.is_nan() || value <= 0.0 {
+                return Err(format!("{name} has to be more than 0, not {value}"));
+            }
+        }
+        let not_negative = [
+            ("paddle_speed", self.paddle_speed),
+            (
+                "gap_between_paddle_and_bricks",
+                self.gap_between_paddle_and_bricks,
+            ),
+            ("gap_between_bricks", self.gap_between_bricks),
+            (
+                "gap_between_bricks_and_ceiling",
+                self.gap_between_bricks_and_ceiling,
+            ),
+            (
+                "gap_between_bricks_and_sides",
+                self.gap_between_bricks_and_sides,
+            ),
+            (
+                "gap_between_paddle_and_bricks_two_sided",
+                self.gap_between_paddle_and_bricks_two_sided,
+            ),
+        ];
+        for (name, value) in not_negative {
+            if value.is_nan() || value < 0.0 {
+                return Err(format!("{name} can't be less than 0, not {value}"));
+            }
+        }
+
+        let arena = Arena {
+            width: MIN_ARENA_SIZE.x,
+            height: MIN_ARENA_SIZE.y,
+        };
+        let width = arena.width - 2.0 * self.gap_between_bricks_and_sides;
+        if width < self.brick_step().x {
+            return Err(format!(
+                "not even one column of bricks fits in the smallest arena, {} wide",
+                arena.width
+            ));
+        }
+        let band = arena.top()
+            - self.gap_between_bricks_and_ceiling
+            - (arena.paddle_y() + self.gap_between_paddle_and_bricks);
+        let two_sided_band = arena.top_paddle_y()
+            - arena.paddle_y()
+            - 2.0 * self.gap_between_paddle_and_bricks_two_sided;
+        if band <= 0.0 {
+            return Err(format!(
+                "there is no room for bricks between the paddle and the ceiling \
+                 of the smallest arena, {} high",
+                arena.height
+            ));
+        }
+        if two_sided_band <= 0.0 {
+            return Err(format!(
+                "there is no room for bricks between the two paddles of the smallest arena, {} high",
+                arena.height
+            ));
+        }
+        Ok(())
+    }
+
+    /// Distance between the centers of neighbouring bricks
+    pub(crate) fn brick_step(&self) -> Vec2 {
+        self.brick_size + Vec2::splat(self.gap_between_bricks)
+    }
+
+    /// The ball's velocity when it is served
+    pub(crate) fn serve_velocity(&self) -> Vec2 {
+        INITIAL_BALL_DIRECTION.normalize() * self.ball_speed
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct TunablesLoader;
+
+impl AssetLoader for TunablesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let tunables: Tunables = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(tunables));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tunables.ron"]
+    }
+}
+
+// Keeps the file loaded, so it gets reloaded when it changes
+#[derive(Resource)]
+pub(crate) struct TunablesHandle(Handle<Tunables>);
+
+pub(crate) fn watch_tunables(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TunablesHandle(asset_server.load(TUNABLES_ASSET)));
+}
+
+// Copies the file into the resource whenever it is saved
+pub(crate) fn reload_tunables(
+    mut events: EventReader<AssetEvent<Tunables>>,
+    assets: Res<Assets<Tunables>>,
+    handle: Res<TunablesHandle>,
+    mut tunables: ResMut<Tunables>,
+) {
+    for event in events.iter() {
+        let (AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }) =
+            event
+        else {
+            continue;
+        };
+        if *changed != handle.0 {
+            continue;
+        }
+        if let Some(loaded) = assets.get(changed) {
+            if *loaded == *tunables {
+                continue;
+            }
+            // Keep playing with the last good ones rather than crash over a typo
+            if let Err(err) = loaded.validate() {
+                warn!("Ignoring the changed tunables: {err}");
+                continue;
+            }
+            *tunables = loaded.clone();
+            info!("Tunables reloaded");
+        }
+    }
+}
+
+// What of a paddle or brick changes with its size, as a query needs to see it
+type PaddleShape = (
+    &'static Player,
+    &'static mut Handle<Mesh>,
+    &'static Handle<StandardMaterial>,
+    &'static mut Collider,
+);
+type BrickShape = (&'static mut Handle<Mesh>, &'static mut Collider);
+
+// Brings what is already on screen in line with changed tunables.
+// Everything spawned later reads them anyway.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn apply_tunables(
+    tunables: Res<Tunables>,
+    mut previous: Local<Option<Tunables>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut brick_assets: ResMut<BrickAssets>,
+    mut line_materials: ResMut<Assets<LineMaterial>>,
+    mut paddle_query: Query<PaddleShape, (With<Paddle>, Without<Brick>)>,
+    mut ball_query: Query<(&mut Velocity, &Handle<StandardMaterial>), With<Ball>>,
+    mut brick_query: Query<BrickShape, (With<Brick>, Without<Paddle>)>,
+    wall_query: Query<&Handle<LineMaterial>, With<Wall>>,
+    mut text_query: Query<&mut Text>,
+) {
+    if !tunables.is_changed() {
+        return;
+    }
+    // Whatever is on screen at first already uses the tunables
+    let Some(old) = previous.replace(tunables.clone()) else {
+        return;
+    };
+    if old == *tunables {
+        return;
+    }
+    let colors = &tunables.colors;
+    clear_color.0 = colors.background;
+
+    let paddle_size = tunables.paddle_size;
+    let paddle_mesh =
+        meshes.add(shape::Box::new(paddle_size.x, paddle_size.y, paddle_size.z).into());
+    for (player, mut mesh, material, mut collider) in &mut paddle_query {
+        *mesh = paddle_mesh.clone();
+        *collider = Collider::Aabb(paddle_size.truncate());
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = player.paddle_color(&tunables);
+        }
+    }
+
+    for (mut velocity, material) in &mut ball_query {
+        velocity.0 = velocity.0.normalize_or_zero() * tunables.ball_speed;
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = colors.ball;
+        }
+    }
+
+    let brick_size = tunables.brick_size;
+    let brick_mesh = brick_assets.mesh(&mut meshes, brick_size);
+    for (mut mesh, mut collider) in &mut brick_query {
+        *mesh = brick_mesh.clone();
+        *collider = Collider::OrientedBox(brick_size);
+    }
+    // Game modes handing bricks their own materials keep their colors
+    brick_assets.recolor(&mut materials, &tunables);
+
+    for material in &wall_query {
+        if let Some(material) = line_materials.get_mut(material) {
+            material.color = colors.wall;
+        }
+    }
+
+    // Texts are spread over every mode and screen, and only ever use these two colors
+    for mut text in &mut text_query {
+        for section in &mut text.sections {
+            if section.style.color == old.colors.text {
+                section.style.color = colors.text;
+            } else if section.style.color == old.colors.score {
+                section.style.color = colors.score;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(Tunables::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_what_the_game_cant_be_played_with() {
+        let broken = [
+            Tunables {
+                brick_size: Vec2::new(0.0, 15.0),
+                ..default()
+            },
+            Tunables {
+                ball_speed: f32::NAN,
+                ..default()
+            },
+            Tunables {
+                gap_between_bricks: -80.0,
+                ..default()
+            },
+            // Not a single brick fits across
+            Tunables {
+                brick_size: Vec2::new(1000.0, 15.0),
+                ..default()
+            },
+            // No room left above the paddle
+            Tunables {
+                gap_between_paddle_and_bricks: 1000.0,
+                ..default()
+            },
+            Tunables {
+                gap_between_paddle_and_bricks_two_sided: 1000.0,
+                ..default()
+            },
+        ];
+        for tunables in broken {
+            assert!(tunables.validate().is_err(), "{tunables:?}");
+        }
+    }
+
+    // The game running with `current`, once the file is changed to `changed`
+    fn reload(current: Tunables, changed: Tunables) -> Tunables {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Tunables>()
+            .insert_resource(current)
+            .add_system(reload_tunables);
+        let handle = app.world.resource_mut::<Assets<Tunables>>().add(changed);
+        app.insert_resource(TunablesHandle(handle));
+        // The asset events only come out at the end of the first update
+        app.update();
+        app.update();
+        app.world.resource::<Tunables>().clone()
+    }
+
+    #[test]
+    fn reloads_valid_tunables() {
+        let faster = Tunables {
+            ball_speed: 400.0,
+            ..default()
+        };
+        assert_eq!(reload(Tunables::default(), faster.clone()), faster);
+    }
+
+    #[test]
+    fn keeps_the_previous_tunables_on_a_bad_reload() {
+        let broken = Tunables {
+            brick_size: Vec2::ZERO,
+            ..default()
+        };
+        assert_eq!(reload(Tunables::default(), broken), Tunables::default());
+    }
+}
@@ -0,0 +1,239 @@
+//! Versus mode: a mix of Pong and Breakout.
+//! Player one defends the bottom wall and player two the top wall,
+//! each behind their own wall of bricks. Letting the ball past, or losing one of the bricks of
+//! their wall, gives the other player a point.
+
+use bevy::prelude::*;
+
+use crate::{
+    arena::Arena, tunables::Tunables, Ball, BallLostEvent, Brick, BrickDestroyedEvent, Player,
+    RoundState, ScoreboardText, Velocity, WallLocation, SCOREBOARD_FONT_SIZE,
+    SCOREBOARD_TEXT_PADDING,
+};
+
+// A match is won by the first player to reach this many points...
+const POINTS_TO_WIN: usize = 7;
+// ...with at least this big a lead
+const WINNING_MARGIN: usize = 2;
+
+impl Player {
+    /// The player defending the given wall, if any
+    fn defending(location: WallLocation) -> Option<Self> {
+        match location {
+            WallLocation::Bottom => Some(Player::One),
+            WallLocation::Top => Some(Player::Two),
+            WallLocation::Left | WallLocation::Right => None,
+        }
+    }
+
+    // The ball is served from in front of the player's paddle, towards them
+    fn serve(self, tunables: &Tunables, arena: &Arena) -> (Vec3, Vec2) {
+        let direction = tunables.serve_velocity();
+        let distance = tunables.gap_between_paddle_and_bricks_two_sided / 2.0;
+        match self {
+            Player::One => (Vec3::new(0.0, arena.paddle_y() + distance, 0.0), direction),
+            Player::Two => (
+                Vec3::new(0.0, arena.top_paddle_y() - distance, 0.0),
+                Vec2::new(direction.x, -direction.y),
+            ),
+        }
+    }
+}
+
+/// Points of both players in the current match
+#[derive(Resource, Clone, Default, Debug)]
+pub(crate) struct VersusScores {
+    points: [usize; 2],
+}
+
+impl VersusScores {
+    pub(crate) fn points(&self, player: Player) -> usize {
+        self.points[player.index()]
+    }
+
+    // Whether the player would win the match by scoring the next point
+    fn has_match_point(&self, player: Player) -> bool {
+        let points = self.points(player) + 1;
+        points >= POINTS_TO_WIN && points >= self.points(player.opponent()) + WINNING_MARGIN
+    }
+
+    fn winner(&self) -> Option<Player> {
+        [Player::One, Player::Two].into_iter().find(|&player| {
+            let points = self.points(player);
+            points >= POINTS_TO_WIN && points >= self.points(player.opponent()) + WINNING_MARGIN
+        })
+    }
+}
+
+// Shows the points of one of the players
+#[derive(Component)]
+pub(crate) struct VersusScoreText(Player);
+
+pub(crate) fn setup_versus(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut brick_query: Query<(Entity, &Transform, &mut Handle<StandardMaterial>), With<Brick>>,
+    mut shared_scoreboard_query: Query<&mut Visibility, With<ScoreboardText>>,
+) {
+    // The bricks closest to a player's paddle make up their wall
+    let player_one_material = materials.add(tunables.colors.player_one_brick.into());
+    let player_two_material = materials.add(tunables.colors.player_two_brick.into());
+    let center_y = (arena.paddle_y() + arena.top_paddle_y()) / 2.0;
+    for (brick, transform, mut material) in &mut brick_query {
+        let owner = if transform.translation.y < center_y {
+            *material = player_one_material.clone();
+            Player::One
+        } else {
+            *material = player_two_material.clone();
+            Player::Two
+        };
+        commands.entity(brick).insert(owner);
+    }
+
+    // Each player gets their own scoreboard on their side of the screen,
+    // in place of the shared score and lives
+    for mut visibility in &mut shared_scoreboard_query {
+        visibility.is_visible = false;
+    }
+
+    for player in [Player::One, Player::Two] {
+        let position = match player {
+            Player::One => UiRect {
+                bottom: SCOREBOARD_TEXT_PADDING,
+                left: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+            Player::Two => UiRect {
+                top: SCOREBOARD_TEXT_PADDING,
+                left: SCOREBOARD_TEXT_PADDING,
+                ..default()
+            },
+        };
+
+        commands.spawn((
+            TextBundle::from_sections([
+                TextSection::new(
+                    format!("{}: ", player.name()),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: SCOREBOARD_FONT_SIZE,
+                        color: tunables.colors.text,
+                    },
+                ),
+                TextSection::from_style(TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: SCOREBOARD_FONT_SIZE,
+                    color: tunables.colors.score,
+                }),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position,
+                ..default()
+            }),
+            VersusScoreText(player),
+        ));
+    }
+}
+
+// Award a point to the opponent of whoever lost a brick or let the ball past,
+// and serve the ball again to whoever let it past
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn score_points(
+    tunables: Res<Tunables>,
+    arena: Res<Arena>,
+    mut scores: ResMut<VersusScores>,
+    mut round: ResMut<RoundState>,
+    mut brick_destroyed_events: EventReader<BrickDestroyedEvent>,
+    mut ball_lost_events: EventReader<BallLostEvent>,
+    // Broken bricks are only despawned once the step is over, so their owner is still there
+    owner_query: Query<&Player, With<Brick>>,
+    wall_query: Query<&WallLocation>,
+    mut ball_query: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+) {
+    for BrickDestroyedEvent(brick) in brick_destroyed_events.iter() {
+        let Ok(owner) = owner_query.get(*brick) else {
+            continue;
+        };
+        scores.points[owner.opponent().index()] += 1;
+        if scores.winner().is_some() {
+            *round = RoundState::GameOver;
+            return;
+        }
+    }
+
+    for event in ball_lost_events.iter() {
+        let Some(conceding) = wall_query
+            .get(event.zone)
+            .ok()
+            .copied()
+            .and_then(Player::defending)
+        else {
+            continue;
+        };
+
+        scores.points[conceding.opponent().index()] += 1;
+        if scores.winner().is_some() {
+            *round = RoundState::GameOver;
+            return;
+        }
+
+        if let Ok((mut transform, mut velocity)) = ball_query.get_mut(event.ball) {
+            let (position, direction) = conceding.serve(&tunables, &arena);
+            transform.translation = position;
+            velocity.0 = direction;
+        }
+    }
+}
+
+pub(crate) fn update_versus_scoreboard(
+    scores: Res<VersusScores>,
+    mut query: Query<(&mut Text, &VersusScoreText)>,
+) {
+    for (mut text, VersusScoreText(player)) in &mut query {
+        let points = scores.points(*player);
+        text.sections[1].value = if scores.has_match_point(*player) {
+            format!("{points}  Match point")
+        } else {
+            points.to_string()
+        };
+    }
+}
+
+pub(crate) fn announce_winner(
+    mut commands: Commands,
+    round: Res<RoundState>,
+    scores: Res<VersusScores>,
+    tunables: Res<Tunables>,
+    asset_server: Res<AssetServer>,
+) {
+    if !round.is_changed() || *round != RoundState::GameOver {
+        return;
+    }
+    let Some(winner) = scores.winner() else {
+        return;
+    };
+
+    commands.spawn(
+        TextBundle::from_section(
+            format!("{} wins!", winner.name()),
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 2.0 * SCOREBOARD_FONT_SIZE,
+                color: tunables.colors.score,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(40.0),
+                left: Val::Percent(35.0),
+                ..default()
+            },
+            ..default()
+        }),
+    );
+}
@@ -13,7 +13,12 @@ use bevy::{
     },
 };
 
-use crate::{ Collider, LEFT_WALL, RIGHT_WALL, TOP_WALL, BOTTOM_WALL, WALL_THICKNESS };
+use crate::{
+    arena::Arena,
+    collision::{Collider, Segment},
+    tunables::Tunables,
+    GameMode, LossZone,
+};
 
 // This bundle is a collection of the components that define a "wall" in our game
 #[derive(Bundle)]
@@ -23,57 +28,83 @@ struct WallBundle {
     // sprite_bundle: SpriteBundle,
     mesh: MaterialMeshBundle<LineMaterial>,
     collider: Collider,
+    wall: Wall,
+    location: WallLocation,
 }
 
+// Marks the arena's walls, as opposed to the other colliders
+#[derive(Component)]
+pub(crate) struct Wall;
+
 /// Which side of the arena is this wall located on?
-enum WallLocation {
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum WallLocation {
     Left,
     Right,
     Bottom,
     Top,
 }
 
+/// The walls going around the arena, as a closed outline.
+/// The points go counterclockwise, so the inside of the arena is to the left of every segment.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub(crate) struct WallOutline {
+    points: Vec<Vec2>,
+}
+
 pub(crate) fn wall_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<LineMaterial>>,
+    tunables: Res<Tunables>,
+    mode: Res<GameMode>,
+    outline: Res<WallOutline>,
 ) {
     // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Right));
     // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Left));
     // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Bottom));
     // commands.spawn(WallBundle::new(&mut meshes, &mut materials, WallLocation::Top));
 
-    for loc in &[WallLocation::Right, WallLocation::Left, WallLocation::Bottom, WallLocation::Top] {
+    spawn_walls(&mut commands, &mut meshes, &mut materials, &tunables, *mode, &outline);
+}
+
+pub(crate) fn spawn_walls(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<LineMaterial>,
+    tunables: &Tunables,
+    mode: GameMode,
+    outline: &WallOutline,
+) {
+    let material = materials.add(LineMaterial {
+        color: tunables.colors.wall,
+    });
+
+    for segment in outline.segments() {
+        let location = WallLocation::facing(segment.normal());
 
-        let (v1, v2) = loc.line();
-        // Spawn a list of lines with start and end points for each lines
-        commands.spawn(
+        // Spawn a line from the start to the end of each segment, where it already is,
+        // so the line drawn on screen is also what the ball bounces off
+        let mut wall = commands.spawn(
             WallBundle {
                 mesh: MaterialMeshBundle {
                     mesh: meshes.add(Mesh::from(LineStrip {
                         points: vec![
-                            v1.extend(0.0),
-                            v2.extend(0.0)
+                            segment.start.extend(0.0),
+                            segment.end.extend(0.0)
                         ],
                     })),
-                    transform: Transform {
-                        //                     // We need to convert our Vec2 into a Vec3, by giving it a z-coordinate
-                        //                     // This is used to determine the order of our sprites
-                        translation: loc.position().extend(0.0),
-                        //                     // The z-scale of 2D objects must always be 1.0,
-                        //                     // or their ordering will be affected in surprising ways.
-                        //                     // See https://github.com/bevyengine/bevy/issues/4149
-                        scale: loc.size().extend(1.0),
-                        ..default()
-                    },
-                    material: materials.add(LineMaterial {
-                        color: Color::GREEN,
-                    }),
+                    material: material.clone(),
                     ..default()
                 },
-                collider: Collider(loc.size())
+                collider: Collider::Segment(segment),
+                wall: Wall,
+                location,
             }
         );
+        if location.out_of_bounds(mode) {
+            wall.insert(LossZone);
+        }
     }
 
 }
@@ -82,7 +113,7 @@ pub(crate) fn wall_setup(
 #[uuid = "050ce6ac-080a-4d8c-b6b5-b5bab7560d8f"]
 pub(crate) struct LineMaterial {
     #[uniform(0)]
-    color: Color,
+    pub(crate) color: Color,
 }
 
 impl Material for LineMaterial {
@@ -139,39 +170,82 @@ impl From<LineStrip> for Mesh {
 
 
 impl WallLocation {
-    fn position(&self) -> Vec2 {
-        match self {
-            WallLocation::Left => Vec2::new(crate::LEFT_WALL, 0.),
-            WallLocation::Right => Vec2::new(crate::RIGHT_WALL, 0.),
-            WallLocation::Bottom => Vec2::new(0., crate::BOTTOM_WALL),
-            WallLocation::Top => Vec2::new(0., crate::TOP_WALL),
+    // Walls facing up more than sideways are the floor, and those facing down the ceiling
+    fn facing(normal: Vec2) -> Self {
+        if normal.y.abs() > normal.x.abs() {
+            if normal.y > 0.0 { WallLocation::Bottom } else { WallLocation::Top }
+        } else if normal.x > 0.0 {
+            WallLocation::Left
+        } else {
+            WallLocation::Right
         }
     }
 
-    fn line(&self) -> (Vec2, Vec2) {
+    // The bottom wall is always out of bounds, and so is the top one when a paddle defends it
+    fn out_of_bounds(self, mode: GameMode) -> bool {
         match self {
-            WallLocation::Left => ( Vec2::new(LEFT_WALL, TOP_WALL), Vec2::new(LEFT_WALL, BOTTOM_WALL) ),
-            WallLocation::Right => ( Vec2::new(RIGHT_WALL, TOP_WALL), Vec2::new(RIGHT_WALL, BOTTOM_WALL) ),
-            WallLocation::Top => ( Vec2::new(LEFT_WALL, TOP_WALL), Vec2::new(RIGHT_WALL, TOP_WALL) ),
-            WallLocation::Bottom => ( Vec2::new(LEFT_WALL, BOTTOM_WALL), Vec2::new(RIGHT_WALL, BOTTOM_WALL) ),
+            // Only modes with lives or goals lose the ball, the others bounce it back up
+            WallLocation::Bottom => matches!(mode, GameMode::CoOp(_) | GameMode::Versus),
+            WallLocation::Top => mode.two_sided(),
+            WallLocation::Left | WallLocation::Right => false,
         }
     }
+}
 
-    fn size(&self) -> Vec2 {
-        let arena_height = TOP_WALL - BOTTOM_WALL;
-        let arena_width = RIGHT_WALL - LEFT_WALL;
-        // Make sure we haven't messed up our constants
-        assert!(arena_height > 0.0);
-        assert!(arena_width > 0.0);
+impl WallOutline {
+    /// The outline through the given points, which may go around either way
+    pub(crate) fn new(mut points: Vec<Vec2>) -> Self {
+        // Closing the outline by repeating the first point is fine, but it makes for an empty segment
+        points.dedup();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
 
-        match self {
-            WallLocation::Left | WallLocation::Right => {
-                Vec2::new(WALL_THICKNESS, arena_height + WALL_THICKNESS)
-            }
-            WallLocation::Bottom | WallLocation::Top => {
-                Vec2::new(arena_width + WALL_THICKNESS, WALL_THICKNESS)
-            }
+        // Twice the area enclosed, negative when going around clockwise
+        let area: f32 = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.perp_dot(*b))
+            .sum();
+        if area < 0.0 {
+            points.reverse();
         }
+        WallOutline { points }
+    }
+
+    /// The four sides of the arena
+    pub(crate) fn rectangle(arena: &Arena) -> Self {
+        WallOutline::new(vec![
+            Vec2::new(arena.left(), arena.bottom()),
+            Vec2::new(arena.right(), arena.bottom()),
+            Vec2::new(arena.right(), arena.top()),
+            Vec2::new(arena.left(), arena.top()),
+        ])
+    }
+
+    pub(crate) fn points(&self) -> &[Vec2] {
+        &self.points
+    }
+
+    pub(crate) fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        self.points
+            .iter()
+            .zip(self.points.iter().cycle().skip(1))
+            .map(|(&start, &end)| Segment { start, end })
+    }
+
+    /// Whether the point is inside the walls
+    pub(crate) fn contains(&self, point: Vec2) -> bool {
+        // Count the segments crossed going right from the point
+        let crossings = self
+            .segments()
+            .filter(|segment| {
+                let (a, b) = (segment.start, segment.end);
+                (a.y > point.y) != (b.y > point.y)
+                    && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            })
+            .count();
+        crossings % 2 == 1
     }
 }
 